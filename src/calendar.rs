@@ -1,14 +1,36 @@
 // 0 = Thursday, January 1, 1970 12:00:00 AM
+//
+// Timestamps are signed, so negative values reach back before 1970. Dates use the proleptic
+// Gregorian calendar, meaning its rules are extended backwards before it was adopted in 1582,
+// and astronomical year numbering, where 1 BC is the year 0 and 2 BC is the year -1.
 #![allow(dead_code)]
 
+mod binary;
+mod business;
+mod format;
+mod iso8601;
+mod leap_seconds;
+mod localize;
+mod range;
+mod rrule;
+#[cfg(feature = "serde")]
+mod serialize;
+mod systems;
+mod tz;
+mod week;
+
+use crate::clock::Clock;
 use std::fmt;
+use std::ops::{Add, Sub};
+use std::time::Duration;
+use systems::{Calendar, Gregorian};
 
-#[derive(Debug, Clone, Copy)]
-struct EpochMS(u64);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct EpochMS(pub(crate) i64);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Date {
-    year: u64,
+    year: i64,
     month: Month,
     day: u8, // Starts with 1
     hour: u8,
@@ -16,7 +38,7 @@ struct Date {
     second: u8,
     millisecond: u16,
     weekday: Weekday,
-    timestamp: u64,
+    timestamp: i64,
     is_leap_year: bool,
 }
 
@@ -38,179 +60,399 @@ impl fmt::Display for Date {
     }
 }
 
+/// The divisions round towards negative infinity, so that -1 is the last millisecond of
+/// December 31, 1969, and not some time on January 1.
 impl EpochMS {
-    const fn seconds_since(&self) -> u64 {
-        self.0 / 1000
+    const fn seconds_since(&self) -> i64 {
+        self.0.div_euclid(1000)
     }
 
-    const fn minutes_since(&self) -> u64 {
-        self.seconds_since() / 60
+    const fn minutes_since(&self) -> i64 {
+        self.seconds_since().div_euclid(60)
     }
 
-    const fn hours_since(&self) -> u64 {
-        self.minutes_since() / 60
+    const fn hours_since(&self) -> i64 {
+        self.minutes_since().div_euclid(60)
     }
 
-    const fn days_since(&self) -> u64 {
-        self.hours_since() / 24
+    const fn days_since(&self) -> i64 {
+        self.hours_since().div_euclid(24)
     }
 
     const fn hour(&self) -> u8 {
-        (self.hours_since() % 24) as u8
+        self.hours_since().rem_euclid(24) as u8
     }
 
     const fn minute(&self) -> u8 {
-        (self.minutes_since() % 60) as u8
+        self.minutes_since().rem_euclid(60) as u8
     }
 
     const fn second(&self) -> u8 {
-        (self.seconds_since() % 60) as u8
+        self.seconds_since().rem_euclid(60) as u8
     }
 
     const fn millisecond(&self) -> u16 {
-        (self.0 % 1000) as u16
+        self.0.rem_euclid(1000) as u16
     }
 }
 
-impl From<EpochMS> for Date {
-    fn from(epoch: EpochMS) -> Date {
-        let mut year = 1970;
-        let mut days = epoch.days_since();
-
-        // Is there a formula that is non-iterative?
-        loop {
-            let days_in_year = get_days_in_year(year);
-            let is_leap_year = days_in_year == 366;
-            let (month, day) = get_day_month(days as u8, is_leap_year);
-
-            if days < days_in_year {
-                return Date {
-                    year,
-                    month,
-                    day: day + 1,
-                    hour: epoch.hour(),
-                    minute: epoch.minute(),
-                    second: epoch.second(),
-                    millisecond: epoch.millisecond(),
-                    weekday: Weekday::from(epoch),
-                    timestamp: epoch.0,
-                    is_leap_year,
-                };
-            }
-            days -= days_in_year;
-            year += 1;
+impl Date {
+    /// Build a date from its calendar fields, validating each one. The fields are
+    /// checked in the same way as converting a [Date] back into an [EpochMS]. The second can
+    /// be 60 at 23:59 for a leap second, as any day could end with one without a table of
+    /// [LeapSeconds](leap_seconds::LeapSeconds) to check against.
+    fn new(
+        year: i64,
+        month: Month,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        millisecond: u16,
+    ) -> Result<Date, DateError> {
+        let epoch = epoch_from_fields(year, month, day, hour, minute, second, millisecond)?;
+        if second == 60 {
+            let date = Date::new(year, month, day, hour, minute, 59, millisecond)?;
+            return Ok(date.into_leap_second());
         }
+        Ok(Date::from(epoch))
+    }
+
+    /// The current date and time in UTC, according to the clock.
+    fn now(clock: &impl Clock) -> Date {
+        Date::from(clock.now())
+    }
+
+    const fn epoch(&self) -> EpochMS {
+        EpochMS(self.timestamp)
+    }
+
+    /// Move the date by a number of calendar months, keeping the time of day. When the day
+    /// doesn't exist in the target month, it is clamped to the end of that month, so
+    /// January 31 plus one month is February 28 (or 29).
+    fn add_months(&self, months: i64) -> Result<Date, DateError> {
+        let (year, month, day) = self.shift_months(months).ok_or(DateError::YearOutOfRange(
+            self.year.saturating_add(months / 12),
+        ))?;
+        Date::new(
+            year,
+            month,
+            day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.millisecond,
+        )
+    }
+
+    /// Move the date by a number of calendar years, clamping February 29 to February 28
+    /// when the target year isn't a leap year.
+    fn add_years(&self, years: i64) -> Result<Date, DateError> {
+        let months = years
+            .checked_mul(12)
+            .ok_or(DateError::YearOutOfRange(self.year.saturating_add(years)))?;
+        self.add_months(months)
+    }
+
+    /// The year, month and clamped day that are a number of months away, or `None` if the
+    /// month count overflows.
+    fn shift_months(&self, months: i64) -> Option<(i64, Month, u8)> {
+        let total_months = self.proleptic_month().checked_add(months)?;
+        let year = total_months.div_euclid(12);
+        let month = Month::ALL[total_months.rem_euclid(12) as usize];
+        let day = self.day.min(month.days(Gregorian.is_leap_year(year)));
+        Some((year, month, day))
     }
-}
 
-/// Every year that is exactly divisible by four is a leap year, except for years that
-/// are exactly divisible by 100, but these centurial years are leap years if they are
-/// exactly divisible by 400. For example, the years 1700, 1800, and 1900 are not leap
-/// years, but the years 1600 and 2000 are.[2]
-fn get_days_in_year(year: u64) -> u64 {
-    let normal_year = 365;
-    let leap_year = 366;
-    if year % 4 != 0 {
-        return normal_year;
+    /// The number of whole calendar days from this date until the other one. The time of day
+    /// is ignored, so 23:00 until 01:00 the next day is still 1 day.
+    const fn days_until(&self, other: &Date) -> i64 {
+        other.epoch().days_since() - self.epoch().days_since()
     }
 
-    if year % 100 == 0 {
-        if year % 400 == 0 {
-            leap_year
-        } else {
-            normal_year
+    /// The difference between two dates in years, months and days. The time of day is
+    /// ignored. All of the fields share the same sign, and adding the months to this date
+    /// and then the days gives back the other date.
+    fn period_until(&self, other: &Date) -> Period {
+        let mut total_months = other.proleptic_month() - self.proleptic_month();
+        let direction = total_months.signum();
+        // The anchor is only needed as a day, so it isn't limited to the years of Date::new,
+        // and can't overflow as it's between the two dates.
+        let days_to_anchor = |months| {
+            let (year, month, day) = self
+                .shift_months(months)
+                .expect("The anchor is between two dates.");
+            other.epoch().days_since() - days_from_civil(year, month, day)
+        };
+        let mut days = days_to_anchor(total_months);
+        // The anchor can go past the other date, when its day is later in the month in the
+        // direction of travel, so that one month less is added.
+        if direction != 0 && days.signum() == -direction {
+            total_months -= direction;
+            days = days_to_anchor(total_months);
+        }
+        Period {
+            years: total_months / 12,
+            months: total_months % 12,
+            days,
         }
-    } else {
-        leap_year
+    }
+
+    /// The number of months since January of the year 0.
+    const fn proleptic_month(&self) -> i64 {
+        self.year * 12 + self.month.number() as i64 - 1
     }
 }
 
-fn get_day_month(day_in_year: u8, is_leap_year: bool) -> (Month, u8) {
-    let days_in_february = if is_leap_year { 29 } else { 38 };
+/// A calendar-based amount of time, such as a billing period, as opposed to an exact
+/// [Duration]. The length of a month depends on which month it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Period {
+    years: i64,
+    months: i64,
+    days: i64,
+}
 
-    let mut days = day_in_year;
+impl EpochMS {
+    /// Add the whole milliseconds of the duration, or `None` if the timestamp overflows.
+    fn checked_add(self, duration: Duration) -> Option<EpochMS> {
+        let millis = i64::try_from(duration.as_millis()).ok()?;
+        self.0.checked_add(millis).map(EpochMS)
+    }
 
-    if days < 31 {
-        return (Month::January, days);
-    } else {
-        days -= 31;
+    fn checked_sub(self, duration: Duration) -> Option<EpochMS> {
+        let millis = i64::try_from(duration.as_millis()).ok()?;
+        self.0.checked_sub(millis).map(EpochMS)
     }
+}
 
-    if days < days_in_february {
-        return (Month::February, days);
-    } else {
-        days -= days_in_february;
+/// Panics if the timestamp overflows, like adding to an [Instant](std::time::Instant). Use
+/// [EpochMS::checked_add] when the duration could be that large.
+impl Add<Duration> for EpochMS {
+    type Output = EpochMS;
+
+    fn add(self, duration: Duration) -> EpochMS {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to timestamp")
     }
+}
 
-    if days < 31 {
-        return (Month::March, days);
-    } else {
-        days -= 31;
+impl Sub<Duration> for EpochMS {
+    type Output = EpochMS;
+
+    fn sub(self, duration: Duration) -> EpochMS {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from timestamp")
     }
+}
 
-    if days < 30 {
-        return (Month::April, days);
-    } else {
-        days -= 30;
+/// Durations are exact, so adding 24 hours always lands on the same time of day.
+impl Add<Duration> for Date {
+    type Output = Date;
+
+    fn add(self, duration: Duration) -> Date {
+        Date::from(self.epoch() + duration)
     }
+}
 
-    if days < 31 {
-        return (Month::May, days);
-    } else {
-        days -= 31;
+impl Sub<Duration> for Date {
+    type Output = Date;
+
+    fn sub(self, duration: Duration) -> Date {
+        Date::from(self.epoch() - duration)
     }
+}
 
-    if days < 30 {
-        return (Month::June, days);
-    } else {
-        days -= 30;
+impl From<EpochMS> for Date {
+    fn from(epoch: EpochMS) -> Date {
+        let (year, month, day) = civil_from_days(epoch.days_since());
+        Date {
+            year,
+            month,
+            day,
+            hour: epoch.hour(),
+            minute: epoch.minute(),
+            second: epoch.second(),
+            millisecond: epoch.millisecond(),
+            weekday: Weekday::from(epoch),
+            timestamp: epoch.0,
+            is_leap_year: Gregorian.is_leap_year(year),
+        }
     }
+}
 
-    if days < 31 {
-        return (Month::July, days);
-    } else {
-        days -= 31;
+impl TryFrom<Date> for EpochMS {
+    type Error = DateError;
+
+    /// The fields of the date are re-validated, as the timestamp is derived from them.
+    fn try_from(date: Date) -> Result<EpochMS, DateError> {
+        epoch_from_fields(
+            date.year,
+            date.month,
+            date.day,
+            date.hour,
+            date.minute,
+            date.second,
+            date.millisecond,
+        )
     }
+}
 
-    if days < 31 {
-        return (Month::August, days);
-    } else {
-        days -= 31;
+/// The reasons why a set of calendar fields can't be turned into a timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateError {
+    /// Months are numbered 1 through 12.
+    InvalidMonth(u8),
+    /// The day doesn't exist in that month, e.g. February 30.
+    InvalidDay {
+        year: i64,
+        month: Month,
+        day: u8,
+    },
+    InvalidHour(u8),
+    InvalidMinute(u8),
+    InvalidSecond(u8),
+    InvalidMillisecond(u16),
+    /// Weekdays are numbered 0 through 6, starting on Sunday.
+    InvalidWeekday(u8),
+    /// The day does not exist in one of the other calendar systems.
+    InvalidCalendarDate {
+        calendar: &'static str,
+        year: i64,
+        month: u8,
+        day: u8,
+    },
+    /// The day of the year is from 1 to 365, or 366 in a leap year.
+    InvalidDayOfYear(u16),
+    /// The week doesn't exist in that week-based year.
+    InvalidWeek(week::Week),
+    /// The year is outside of [MIN_YEAR] and [MAX_YEAR].
+    YearOutOfRange(i64),
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateError::InvalidMonth(month) => write!(f, "{} is not a valid month", month),
+            DateError::InvalidDay { year, month, day } => {
+                write!(f, "{} {}, {} does not exist", month, day, year)
+            }
+            DateError::InvalidHour(hour) => write!(f, "{} is not a valid hour", hour),
+            DateError::InvalidMinute(minute) => write!(f, "{} is not a valid minute", minute),
+            DateError::InvalidSecond(second) => write!(f, "{} is not a valid second", second),
+            DateError::InvalidMillisecond(ms) => write!(f, "{} is not a valid millisecond", ms),
+            DateError::InvalidWeekday(weekday) => write!(f, "{} is not a valid weekday", weekday),
+            DateError::InvalidCalendarDate {
+                calendar,
+                year,
+                month,
+                day,
+            } => write!(
+                f,
+                "{}-{:02}-{:02} does not exist in the {} calendar",
+                year, month, day, calendar
+            ),
+            DateError::InvalidDayOfYear(day) => write!(f, "{} is not a valid day of the year", day),
+            DateError::InvalidWeek(week) => write!(f, "the week {} does not exist", week),
+            DateError::YearOutOfRange(year) => write!(f, "the year {} is out of range", year),
+        }
     }
+}
 
-    if days < 30 {
-        return (Month::September, days);
-    } else {
-        days -= 30;
+impl std::error::Error for DateError {}
+
+/// The range of years that can be built from calendar fields. Timestamps could reach further,
+/// but this is already plenty for historical data, and keeps the arithmetic from overflowing.
+const MIN_YEAR: i64 = -1_000_000;
+const MAX_YEAR: i64 = 1_000_000;
+
+fn epoch_from_fields(
+    year: i64,
+    month: Month,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    millisecond: u16,
+) -> Result<EpochMS, DateError> {
+    if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+        return Err(DateError::YearOutOfRange(year));
+    }
+    let is_leap_year = Gregorian.is_leap_year(year);
+    if day == 0 || day > month.days(is_leap_year) {
+        return Err(DateError::InvalidDay { year, month, day });
+    }
+    if hour > 23 {
+        return Err(DateError::InvalidHour(hour));
+    }
+    if minute > 59 {
+        return Err(DateError::InvalidMinute(minute));
+    }
+    let is_leap_second = second == 60 && hour == 23 && minute == 59;
+    if second > 59 && !is_leap_second {
+        return Err(DateError::InvalidSecond(second));
+    }
+    if millisecond > 999 {
+        return Err(DateError::InvalidMillisecond(millisecond));
     }
 
-    if days < 31 {
-        return (Month::October, days);
-    } else {
-        days -= 31;
+    let days = days_from_civil(year, month, day);
+    let seconds = ((days * 24 + hour as i64) * 60 + minute as i64) * 60 + second as i64;
+    if is_leap_second {
+        // POSIX time waits at the start of the next day for the whole of a leap second.
+        return Ok(EpochMS(seconds * 1000));
     }
+    Ok(EpochMS(seconds * 1000 + millisecond as i64))
+}
 
-    if days < 30 {
-        return (Month::November, days);
+/// Converts the days since January 1, 1970 into a year, month and day, in constant time.
+/// This is Howard Hinnant's `civil_from_days` algorithm, which shifts the year to start in
+/// March so that the leap day falls at the very end, and then works in 400 year eras.
+/// See: http://howardhinnant.github.io/date_algorithms.html
+const fn civil_from_days(days: i64) -> (i64, Month, u8) {
+    // Shift the epoch from 1970-01-01 to 0000-03-01.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097); // [0, 146096]
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365; // [0, 399]
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100); // [0, 365]
+    let shifted_month = (5 * day_of_year + 2) / 153; // [0, 11], starting with March
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1; // [1, 31]
+    let month = if shifted_month < 10 {
+        shifted_month + 3
     } else {
-        days -= 30;
-    }
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, Month::ALL[month as usize - 1], day as u8)
+}
 
-    (Month::December, days)
+/// The inverse of [civil_from_days], Howard Hinnant's `days_from_civil` algorithm. The fields
+/// are not validated.
+const fn days_from_civil(year: i64, month: Month, day: u8) -> i64 {
+    let month = month.number() as i64;
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400); // [0, 399]
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 }; // [0, 11]
+    let day_of_year = (153 * shifted_month + 2) / 5 + day as i64 - 1; // [0, 365]
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year; // [0, 146096]
+    era * 146_097 + day_of_era - 719_468
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Weekday {
     Sunday,
     Monday,
-    Tueday,
+    Tuesday,
     Wednesday,
     Thursday,
     Friday,
     Saturday,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Month {
     January,
     February,
@@ -226,6 +468,55 @@ enum Month {
     December,
 }
 
+impl Month {
+    const ALL: [Month; 12] = [
+        Month::January,
+        Month::February,
+        Month::March,
+        Month::April,
+        Month::May,
+        Month::June,
+        Month::July,
+        Month::August,
+        Month::September,
+        Month::October,
+        Month::November,
+        Month::December,
+    ];
+
+    /// Months are numbered starting at 1 for January.
+    const fn number(&self) -> u8 {
+        *self as u8 + 1
+    }
+
+    /// The number of days in this month.
+    const fn days(&self, is_leap_year: bool) -> u8 {
+        match self {
+            Month::February => {
+                if is_leap_year {
+                    29
+                } else {
+                    28
+                }
+            }
+            Month::April | Month::June | Month::September | Month::November => 30,
+            _ => 31,
+        }
+    }
+}
+
+/// Months are numbered starting at 1 for January.
+impl TryFrom<u8> for Month {
+    type Error = DateError;
+
+    fn try_from(number: u8) -> Result<Month, DateError> {
+        match number {
+            1..=12 => Ok(Month::ALL[number as usize - 1]),
+            _ => Err(DateError::InvalidMonth(number)),
+        }
+    }
+}
+
 impl fmt::Display for Month {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Sunday, February 1, 1976 15:56:09:0000
@@ -250,15 +541,37 @@ impl fmt::Display for Month {
     }
 }
 
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Sunday,
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+    ];
+
+    /// Sunday is 0 and Saturday is 6.
+    const fn number_from_sunday(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Monday is 1 and Sunday is 7, as in ISO 8601.
+    const fn number_from_monday(&self) -> u8 {
+        (*self as u8 + 6) % 7 + 1
+    }
+}
+
 impl From<EpochMS> for Weekday {
     fn from(day: EpochMS) -> Weekday {
-        match day.days_since() % 7 {
+        match day.days_since().rem_euclid(7) {
             0 => Weekday::Thursday, // 0 = Thursday, January 1, 1970 12:00:00 AM
             1 => Weekday::Friday,
             2 => Weekday::Saturday,
             3 => Weekday::Sunday,
             4 => Weekday::Monday,
-            5 => Weekday::Tueday,
+            5 => Weekday::Tuesday,
             6 => Weekday::Wednesday,
             _ => panic!(),
         }
@@ -274,7 +587,7 @@ impl fmt::Display for Weekday {
             match self {
                 Weekday::Sunday => "Sunday",
                 Weekday::Monday => "Monday",
-                Weekday::Tueday => "Tueday",
+                Weekday::Tuesday => "Tuesday",
                 Weekday::Wednesday => "Wednesday",
                 Weekday::Thursday => "Thursday",
                 Weekday::Friday => "Friday",
@@ -286,9 +599,351 @@ impl fmt::Display for Weekday {
 
 #[test]
 fn test_date() {
-    let date = Date::from(EpochMS(0_192_038_169_123));
+    let date = Date::from(EpochMS(192_038_169_123));
     assert_eq!(format!("{}", date), "Sunday, February 1, 1976 15:56:09:123");
 
     let date = Date::from(EpochMS(1_612_199_945_123));
     assert_eq!(format!("{}", date), "Monday, February 1, 2021 17:19:05:123");
+
+    let clock = crate::clock::MockClock::new(EpochMS(1_612_199_945_123));
+    assert_eq!(Date::now(&clock), date);
+    clock.advance(Duration::from_secs(60 * 60 * 24));
+    assert_eq!(
+        format!("{}", Date::now(&clock)),
+        "Tuesday, February 2, 2021 17:19:05:123"
+    );
+}
+
+#[test]
+fn test_date_round_trip() {
+    let date = Date::new(2021, Month::February, 1, 17, 19, 5, 123).unwrap();
+    assert_eq!(format!("{}", date), "Monday, February 1, 2021 17:19:05:123");
+    assert_eq!(EpochMS::try_from(date).unwrap().0, 1_612_199_945_123);
+
+    for timestamp in [0, 951_782_400_000, 1_609_372_800_000, 4_102_444_799_999] {
+        let epoch = EpochMS::try_from(Date::from(EpochMS(timestamp))).unwrap();
+        assert_eq!(epoch.0, timestamp, "Round trips {}", timestamp);
+    }
+}
+
+#[test]
+fn test_civil_days() {
+    assert_eq!(civil_from_days(0), (1970, Month::January, 1));
+    assert_eq!(days_from_civil(1970, Month::January, 1), 0);
+    assert_eq!(civil_from_days(11_016), (2000, Month::February, 29));
+    assert_eq!(civil_from_days(2_932_896), (9999, Month::December, 31));
+
+    // The closed form agrees with walking the calendar one year at a time.
+    for days in (0..800_000).step_by(97) {
+        let epoch = EpochMS(days * 86_400_000);
+        let date = Date::from(epoch);
+        let expected = date_from_epoch_iterative(epoch);
+        assert_eq!(
+            (date.year, date.month, date.day),
+            (expected.year, expected.month, expected.day)
+        );
+        assert_eq!(days_from_civil(date.year, date.month, date.day), days);
+    }
+}
+
+#[test]
+fn test_date_before_1970() {
+    let date = Date::from(EpochMS(-1));
+    assert_eq!(
+        format!("{}", date),
+        "Wednesday, December 31, 1969 23:59:59:999"
+    );
+
+    // The Gregorian calendar was adopted on Friday, October 15, 1582.
+    let date = Date::new(1582, Month::October, 15, 12, 0, 0, 0).unwrap();
+    assert_eq!(format!("{}", date), "Friday, October 15, 1582 12:00:00:000");
+    assert_eq!(date.timestamp, -12_219_249_600_000);
+
+    // The proleptic calendar carries on from there.
+    let date = Date::new(1582, Month::October, 4, 0, 0, 0, 0).unwrap();
+    assert_eq!(date.weekday, Weekday::Monday);
+
+    // 1 BC is the year 0, and it was a leap year.
+    let date = Date::new(0, Month::February, 29, 0, 0, 0, 0).unwrap();
+    assert!(date.is_leap_year);
+    assert_eq!(format!("{}", date), "Tuesday, February 29, 0 00:00:00:000");
+
+    let date = Date::new(-44, Month::March, 15, 0, 0, 0, 0).unwrap();
+    assert_eq!(format!("{}", date), "Thursday, March 15, -44 00:00:00:000");
+    assert_eq!(
+        EpochMS::try_from(date).unwrap().0,
+        days_from_civil(-44, Month::March, 15) * 86_400_000
+    );
+
+    for timestamp in [
+        -1,
+        -86_400_000,
+        -86_400_001,
+        -62_167_219_200_000,
+        -100_000_000_000_000,
+    ] {
+        let epoch = EpochMS::try_from(Date::from(EpochMS(timestamp))).unwrap();
+        assert_eq!(epoch.0, timestamp, "Round trips {}", timestamp);
+    }
+}
+
+#[test]
+fn test_date_duration_arithmetic() {
+    let date = Date::new(2021, Month::February, 28, 23, 30, 0, 0).unwrap();
+    let later = date.clone() + Duration::from_secs(60 * 60);
+    assert_eq!(format!("{}", later), "Monday, March 1, 2021 00:30:00:000");
+    assert_eq!(later - Duration::from_secs(60 * 60), date);
+
+    let date = Date::from(EpochMS(0)) - Duration::from_millis(1);
+    assert_eq!(
+        format!("{}", date),
+        "Wednesday, December 31, 1969 23:59:59:999"
+    );
+
+    let epoch = EpochMS(0);
+    assert_eq!(
+        epoch.checked_add(Duration::from_millis(1_500)),
+        Some(EpochMS(1_500))
+    );
+    assert_eq!(
+        epoch.checked_sub(Duration::from_micros(1_500)),
+        Some(EpochMS(-1))
+    );
+    assert_eq!(
+        EpochMS(i64::MAX).checked_add(Duration::from_millis(1)),
+        None
+    );
+    assert_eq!(
+        EpochMS(i64::MIN).checked_sub(Duration::from_millis(1)),
+        None
+    );
+    // The milliseconds of the largest duration don't fit in an i64 at all.
+    assert_eq!(epoch.checked_add(Duration::MAX), None);
+    assert_eq!(epoch.checked_sub(Duration::MAX), None);
+}
+
+#[test]
+#[should_panic(expected = "overflow when adding duration to timestamp")]
+fn test_epoch_add_overflow() {
+    let _ = EpochMS(i64::MAX - 1) + Duration::from_secs(1);
+}
+
+#[test]
+fn test_date_calendar_arithmetic() {
+    let date = Date::new(2021, Month::January, 31, 12, 0, 0, 0).unwrap();
+    let expected = Date::new(2021, Month::February, 28, 12, 0, 0, 0).unwrap();
+    assert_eq!(
+        date.add_months(1).unwrap(),
+        expected,
+        "Clamps to the end of month"
+    );
+
+    let leap = Date::new(2020, Month::January, 31, 0, 0, 0, 0).unwrap();
+    assert_eq!(leap.add_months(1).unwrap().day, 29);
+    assert_eq!(leap.add_months(13).unwrap().day, 28);
+    assert_eq!(leap.add_months(-2).unwrap().month, Month::November);
+    assert_eq!(leap.add_months(-2).unwrap().day, 30);
+    assert_eq!(leap.add_months(-2).unwrap().year, 2019);
+
+    let leap_day = Date::new(2020, Month::February, 29, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        leap_day.add_years(1).unwrap(),
+        Date::new(2021, Month::February, 28, 0, 0, 0, 0).unwrap()
+    );
+    assert_eq!(leap_day.add_years(4).unwrap().day, 29);
+    assert_eq!(leap_day.add_years(-2020).unwrap().year, 0);
+    assert!(leap_day.add_years(2_000_000).is_err());
+    assert_eq!(
+        leap_day.add_months(i64::MAX),
+        Err(DateError::YearOutOfRange(2020 + i64::MAX / 12))
+    );
+    assert_eq!(
+        leap_day.add_years(i64::MAX / 6),
+        Err(DateError::YearOutOfRange(2020 + i64::MAX / 6))
+    );
+    assert_eq!(
+        leap_day.add_years(i64::MIN),
+        Err(DateError::YearOutOfRange(2020 + i64::MIN))
+    );
+}
+
+#[test]
+fn test_date_differences() {
+    let start = Date::new(2020, Month::January, 31, 23, 0, 0, 0).unwrap();
+    let end = Date::new(2021, Month::March, 1, 1, 0, 0, 0).unwrap();
+    assert_eq!(start.days_until(&end), 395);
+    assert_eq!(end.days_until(&start), -395);
+
+    let period = start.period_until(&end);
+    assert_eq!(
+        period,
+        Period {
+            years: 1,
+            months: 1,
+            days: 1
+        }
+    );
+    assert_eq!(
+        end.period_until(&start),
+        Period {
+            years: -1,
+            months: -1,
+            days: -1
+        }
+    );
+
+    // A billing period from the 15th to the 14th is just short of a month.
+    let start = Date::new(2021, Month::January, 15, 0, 0, 0, 0).unwrap();
+    let end = Date::new(2021, Month::February, 14, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        start.period_until(&end),
+        Period {
+            years: 0,
+            months: 0,
+            days: 30
+        }
+    );
+    assert_eq!(
+        start.period_until(&start),
+        Period {
+            years: 0,
+            months: 0,
+            days: 0
+        }
+    );
+
+    // Going back, the day is clamped in the month before the other date, not after it.
+    let start = Date::new(2021, Month::March, 31, 0, 0, 0, 0).unwrap();
+    let end = Date::new(2021, Month::February, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        start.period_until(&end),
+        Period {
+            years: 0,
+            months: -1,
+            days: -27
+        }
+    );
+    let end = Date::new(2020, Month::February, 29, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        start.period_until(&end),
+        Period {
+            years: -1,
+            months: -1,
+            days: 0
+        }
+    );
+    let start = Date::new(2021, Month::March, 1, 0, 0, 0, 0).unwrap();
+    let end = Date::new(2021, Month::January, 31, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        start.period_until(&end),
+        Period {
+            years: 0,
+            months: -1,
+            days: -1
+        }
+    );
+
+    // Adding the period always gives back the other date, in either direction.
+    let dates: Vec<_> = [
+        (2020, 1, 31),
+        (2020, 2, 29),
+        (2021, 3, 31),
+        (2021, 4, 30),
+        (2022, 1, 1),
+    ]
+    .into_iter()
+    .map(|(year, month, day)| {
+        Date::new(year, Month::try_from(month).unwrap(), day, 0, 0, 0, 0).unwrap()
+    })
+    .collect();
+    for start in &dates {
+        for end in &dates {
+            let period = start.period_until(end);
+            let months = period.years * 12 + period.months;
+            assert!(months.signum() * period.days.signum() >= 0, "{:?}", period);
+            let anchor = start.add_months(months).unwrap();
+            assert_eq!(anchor.days_until(end), period.days, "{} to {}", start, end);
+        }
+    }
+
+    // A date from a timestamp can be further out than Date::new allows.
+    let start = Date::new(2020, Month::February, 29, 0, 0, 0, 0).unwrap();
+    let end = Date::from(EpochMS(i64::MAX / 2));
+    assert_eq!(end.year, 146_140_482);
+    let period = start.period_until(&end);
+    assert_eq!(period.years, 146_140_482 - 2020);
+    assert!((0..31).contains(&period.days), "{:?}", period);
+    let period = end.period_until(&start);
+    assert_eq!(period.years, 2020 - 146_140_482);
+    assert!((-30..=0).contains(&period.days), "{:?}", period);
+}
+
+#[test]
+fn test_date_validation() {
+    assert_eq!(
+        Date::new(2021, Month::February, 30, 0, 0, 0, 0).unwrap_err(),
+        DateError::InvalidDay {
+            year: 2021,
+            month: Month::February,
+            day: 30
+        }
+    );
+    assert!(Date::new(2020, Month::February, 29, 0, 0, 0, 0).is_ok());
+    assert!(Date::new(2021, Month::February, 29, 0, 0, 0, 0).is_err());
+    assert!(Date::new(2100, Month::February, 29, 0, 0, 0, 0).is_err());
+    assert!(Date::new(2021, Month::April, 31, 0, 0, 0, 0).is_err());
+    assert!(Date::new(2021, Month::April, 0, 0, 0, 0, 0).is_err());
+    assert_eq!(
+        Date::new(2021, Month::April, 1, 24, 0, 0, 0).unwrap_err(),
+        DateError::InvalidHour(24)
+    );
+    assert_eq!(
+        Date::new(1_000_001, Month::January, 1, 0, 0, 0, 0).unwrap_err(),
+        DateError::YearOutOfRange(1_000_001)
+    );
+    assert_eq!(Month::try_from(13), Err(DateError::InvalidMonth(13)));
+    assert_eq!(Month::try_from(12), Ok(Month::December));
+}
+
+/// The original year-by-year conversion, kept around to check the closed form against.
+#[cfg(test)]
+fn date_from_epoch_iterative(epoch: EpochMS) -> Date {
+    let mut year = 1970;
+    let mut days = epoch.days_since();
+    assert!(days >= 0, "Only dates after 1970 are supported.");
+
+    loop {
+        let days_in_year = Gregorian.days_in_year(year) as i64;
+        let is_leap_year = days_in_year == 366;
+
+        if days < days_in_year {
+            let (month, day) = Gregorian.day_month(year, days as u16 + 1);
+            return Date {
+                year,
+                month: Month::ALL[month as usize - 1],
+                day,
+                hour: epoch.hour(),
+                minute: epoch.minute(),
+                second: epoch.second(),
+                millisecond: epoch.millisecond(),
+                weekday: Weekday::from(epoch),
+                timestamp: epoch.0,
+                is_leap_year,
+            };
+        }
+        days -= days_in_year;
+        year += 1;
+    }
+}
+
+/// Both conversions for the benchmarks in `icu_test`, where the [Date] they return can't
+/// be named.
+#[cfg(test)]
+pub(crate) fn bench_closed_form(epoch: EpochMS) -> impl Sized {
+    Date::from(epoch)
+}
+
+#[cfg(test)]
+pub(crate) fn bench_iterative(epoch: EpochMS) -> impl Sized {
+    date_from_epoch_iterative(epoch)
 }
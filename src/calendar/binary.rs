@@ -0,0 +1,165 @@
+//! A compact fixed-width binary encoding of the calendar types, for embedding them in
+//! message formats. Everything is big-endian, and arranged so that comparing the encoded
+//! bytes gives the same order as comparing the values.
+
+use super::{Date, DateError, EpochMS, Month, Weekday, MAX_YEAR, MIN_YEAR};
+
+/// Years are stored offset by this much so that they are never negative. It is larger than
+/// the largest supported year and fits in 21 bits.
+const YEAR_BIAS: i64 = 1 << 20;
+
+impl EpochMS {
+    /// The timestamp as 8 bytes. The sign bit is flipped, so that negative timestamps sort
+    /// before positive ones.
+    pub fn to_bytes(self) -> [u8; 8] {
+        ((self.0 as u64) ^ (1 << 63)).to_be_bytes()
+    }
+
+    pub fn from_bytes(bytes: [u8; 8]) -> EpochMS {
+        EpochMS((u64::from_be_bytes(bytes) ^ (1 << 63)) as i64)
+    }
+}
+
+impl Date {
+    /// The fields packed into 8 bytes, from the most significant bits: the biased year (21),
+    /// month (4), day (5), hour (5), minute (6), second (6) and millisecond (10). The fields
+    /// are stored rather than the timestamp so that a leap second survives the trip. A date
+    /// from a timestamp can be further out than [Date::new] allows, and those years don't fit.
+    pub fn to_bytes(&self) -> Result<[u8; 8], DateError> {
+        if !(MIN_YEAR..=MAX_YEAR).contains(&self.year) {
+            return Err(DateError::YearOutOfRange(self.year));
+        }
+        let fields = [
+            ((self.year + YEAR_BIAS) as u64, 21),
+            (self.month.number() as u64, 4),
+            (self.day as u64, 5),
+            (self.hour as u64, 5),
+            (self.minute as u64, 6),
+            (self.second as u64, 6),
+            (self.millisecond as u64, 10),
+        ];
+        let mut packed = 0;
+        for (value, bits) in fields {
+            packed = (packed << bits) | value;
+        }
+        Ok(packed.to_be_bytes())
+    }
+
+    /// Decode the fields, validating them in the same way as [Date::new].
+    pub fn from_bytes(bytes: [u8; 8]) -> Result<Date, DateError> {
+        let mut packed = u64::from_be_bytes(bytes);
+        let mut take = |bits: u32| {
+            let value = packed & ((1 << bits) - 1);
+            packed >>= bits;
+            value
+        };
+        let millisecond = take(10) as u16;
+        let second = take(6) as u8;
+        let minute = take(6) as u8;
+        let hour = take(5) as u8;
+        let day = take(5) as u8;
+        let month = Month::try_from(take(4) as u8)?;
+        let year = take(21) as i64 - YEAR_BIAS;
+        if packed != 0 {
+            return Err(DateError::YearOutOfRange(year));
+        }
+
+        Date::new(year, month, day, hour, minute, second, millisecond)
+    }
+}
+
+impl Month {
+    /// The month number, starting with 1 for January.
+    pub fn to_bytes(self) -> [u8; 1] {
+        [self.number()]
+    }
+
+    pub fn from_bytes(bytes: [u8; 1]) -> Result<Month, DateError> {
+        Month::try_from(bytes[0])
+    }
+}
+
+impl Weekday {
+    /// The weekday number, starting with 0 for Sunday.
+    pub fn to_bytes(self) -> [u8; 1] {
+        [self.number_from_sunday()]
+    }
+
+    pub fn from_bytes(bytes: [u8; 1]) -> Result<Weekday, DateError> {
+        Weekday::ALL
+            .get(bytes[0] as usize)
+            .copied()
+            .ok_or(DateError::InvalidWeekday(bytes[0]))
+    }
+}
+
+#[test]
+fn test_epoch_bytes() {
+    for timestamp in [
+        0,
+        1,
+        -1,
+        1_612_199_945_123,
+        -62_167_219_200_000,
+        i64::MIN,
+        i64::MAX,
+    ] {
+        let epoch = EpochMS(timestamp);
+        assert_eq!(EpochMS::from_bytes(epoch.to_bytes()), epoch);
+    }
+    assert_eq!(EpochMS(0).to_bytes(), [0x80, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(EpochMS(-1).to_bytes() < EpochMS(0).to_bytes());
+    assert!(EpochMS(i64::MIN).to_bytes() < EpochMS(-1).to_bytes());
+    assert!(EpochMS(1).to_bytes() < EpochMS(i64::MAX).to_bytes());
+}
+
+#[test]
+fn test_date_bytes() {
+    let dates = [
+        Date::new(super::MAX_YEAR, Month::December, 31, 23, 59, 59, 999).unwrap(),
+        Date::new(super::MIN_YEAR, Month::January, 1, 0, 0, 0, 0).unwrap(),
+        Date::new(-44, Month::March, 15, 12, 0, 0, 0).unwrap(),
+        Date::new(2021, Month::February, 1, 17, 19, 5, 123).unwrap(),
+        Date::new(2021, Month::February, 1, 17, 19, 5, 124).unwrap(),
+        Date::new(2016, Month::December, 31, 23, 59, 59, 500)
+            .unwrap()
+            .into_leap_second(),
+    ];
+    for date in &dates {
+        assert_eq!(
+            Date::from_bytes(date.to_bytes().unwrap()).as_ref(),
+            Ok(date)
+        );
+    }
+
+    let mut sorted = dates.to_vec();
+    sorted.sort_by_key(|date| date.to_bytes().unwrap());
+    let years: Vec<_> = sorted.iter().map(|date| date.year).collect();
+    assert_eq!(
+        years,
+        [super::MIN_YEAR, -44, 2016, 2021, 2021, super::MAX_YEAR]
+    );
+    assert_eq!(sorted[3].millisecond, 123);
+
+    // The month is 0 here.
+    let mut bytes = dates[3].to_bytes().unwrap();
+    bytes[3] &= 0xf0;
+    assert_eq!(Date::from_bytes(bytes), Err(DateError::InvalidMonth(0)));
+    assert!(Date::from_bytes([0xff; 8]).is_err());
+
+    // A timestamp can reach years that don't fit.
+    let far = Date::from(EpochMS(i64::MAX));
+    assert_eq!(far.to_bytes(), Err(DateError::YearOutOfRange(far.year)));
+}
+
+#[test]
+fn test_month_weekday_bytes() {
+    for month in Month::ALL {
+        assert_eq!(Month::from_bytes(month.to_bytes()), Ok(month));
+    }
+    for weekday in Weekday::ALL {
+        assert_eq!(Weekday::from_bytes(weekday.to_bytes()), Ok(weekday));
+    }
+    assert_eq!(Month::from_bytes([13]), Err(DateError::InvalidMonth(13)));
+    assert_eq!(Weekday::from_bytes([7]), Err(DateError::InvalidWeekday(7)));
+}
@@ -0,0 +1,611 @@
+//! Business days, which are the days that are neither on the weekend nor a holiday. The
+//! weekend is configurable, and holidays are either single dates, the same date every year,
+//! a rule like the fourth Thursday of November, or an iCalendar recurrence.
+//!
+//! Holidays can be loaded from a text file with one holiday per line, e.g.
+//!
+//! ```text
+//! # Comments start with a hash.
+//! 2021-12-24: Christmas Eve
+//! --12-25: Christmas Day
+//! fourth Thursday of November: Thanksgiving
+//! last Monday of May: Memorial Day
+//! ```
+//!
+//! or from the VEVENTs of an ICS file, using their DTSTART, RRULE, EXDATE and SUMMARY.
+
+use super::rrule::{Occurrences, Recurrence, RecurrenceRule};
+use super::{civil_from_days, days_from_civil, Date, EpochMS, Month, Weekday};
+use std::{fmt, fs, io, path::Path, sync::Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HolidayRule {
+    /// A single day.
+    Date(i64, Month, u8),
+    /// The same month and day every year.
+    Annual(Month, u8),
+    /// The nth weekday of a month, where -1 is the last.
+    NthWeekday(i8, Weekday, Month),
+    /// Any day with an occurrence of the recurrence.
+    Recurrence(Recurrence, OccurrenceCache),
+}
+
+/// The days that a recurrence occurs on. A recurrence can only be expanded from its start,
+/// so the days are kept as they are found, a year at a time.
+#[derive(Debug, Default)]
+pub struct OccurrenceCache {
+    days: Mutex<Option<Box<CachedDays>>>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedDays {
+    occurrences: Occurrences,
+    /// The days with an occurrence before `expanded_until`, in order.
+    days: Vec<i64>,
+    /// The first day of the year after the last one that was expanded.
+    expanded_until: i64,
+    /// An occurrence that was taken from the iterator, but is in a later year.
+    next: Option<i64>,
+}
+
+impl OccurrenceCache {
+    fn occurs_on(&self, recurrence: &Recurrence, day: i64) -> bool {
+        let mut days = self.days.lock().expect("Failed to lock the occurrences.");
+        let days = days.get_or_insert_with(|| {
+            Box::new(CachedDays {
+                occurrences: recurrence.occurrences(),
+                days: Vec::new(),
+                expanded_until: i64::MIN,
+                next: None,
+            })
+        });
+        days.expand_through(day);
+        days.days.binary_search(&day).is_ok()
+    }
+}
+
+impl CachedDays {
+    /// Expand the occurrences up to the end of the year with the day in it.
+    fn expand_through(&mut self, day: i64) {
+        if day < self.expanded_until {
+            return;
+        }
+        let (year, _, _) = civil_from_days(day);
+        self.expanded_until = days_from_civil(year + 1, Month::January, 1);
+        loop {
+            let next = self.next.take().or_else(|| {
+                let date = self.occurrences.next()?;
+                Some(date.epoch().days_since())
+            });
+            let Some(next) = next else {
+                return;
+            };
+            if next >= self.expanded_until {
+                self.next = Some(next);
+                return;
+            }
+            if self.days.last() != Some(&next) {
+                self.days.push(next);
+            }
+        }
+    }
+}
+
+impl Clone for OccurrenceCache {
+    fn clone(&self) -> Self {
+        let days = self.days.lock().expect("Failed to lock the occurrences.");
+        OccurrenceCache {
+            days: Mutex::new(days.clone()),
+        }
+    }
+}
+
+/// The cache only depends on the recurrence, which is compared on its own.
+impl PartialEq for OccurrenceCache {
+    fn eq(&self, _: &OccurrenceCache) -> bool {
+        true
+    }
+}
+
+impl Eq for OccurrenceCache {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holiday {
+    pub name: String,
+    pub rule: HolidayRule,
+}
+
+#[derive(Debug)]
+pub enum HolidayError {
+    Io(io::Error),
+    /// A line couldn't be parsed, with its line number starting at 1.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for HolidayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HolidayError::Io(error) => write!(f, "unable to read the holidays: {}", error),
+            HolidayError::InvalidLine(line) => write!(f, "line {} is invalid", line),
+        }
+    }
+}
+
+impl std::error::Error for HolidayError {}
+
+impl From<io::Error> for HolidayError {
+    fn from(error: io::Error) -> HolidayError {
+        HolidayError::Io(error)
+    }
+}
+
+impl HolidayRule {
+    pub fn occurs_on(&self, date: &Date) -> bool {
+        match self {
+            HolidayRule::Date(year, month, day) => {
+                (date.year, date.month, date.day) == (*year, *month, *day)
+            }
+            HolidayRule::Annual(month, day) => (date.month, date.day) == (*month, *day),
+            HolidayRule::NthWeekday(nth, weekday, month) => {
+                if (date.weekday, date.month) != (*weekday, *month) {
+                    return false;
+                }
+                let days_in_month = month.days(date.is_leap_year);
+                if *nth > 0 {
+                    (date.day as i8 - 1) / 7 + 1 == *nth
+                } else {
+                    (days_in_month as i8 - date.day as i8) / 7 + 1 == -*nth
+                }
+            }
+            HolidayRule::Recurrence(recurrence, cache) => {
+                cache.occurs_on(recurrence, date.epoch().days_since())
+            }
+        }
+    }
+
+    /// Parse the text form of a rule, such as `2021-12-24`, `--12-25` or
+    /// `fourth Thursday of November`.
+    fn parse(text: &str) -> Option<HolidayRule> {
+        if let Some(month_day) = text.strip_prefix("--") {
+            let (month, day) = month_day.split_once('-')?;
+            let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+            let day = day.parse::<u8>().ok()?;
+            // Allow February 29, which only happens in leap years.
+            if day == 0 || day > month.days(true) {
+                return None;
+            }
+            return Some(HolidayRule::Annual(month, day));
+        }
+        if !text.contains(char::is_whitespace) {
+            let date = text.parse::<Date>().ok()?;
+            return Some(HolidayRule::Date(date.year, date.month, date.day));
+        }
+
+        let words: Vec<_> = text.split_whitespace().collect();
+        let (ordinal, weekday, month) = match words.as_slice() {
+            [ordinal, weekday, "of" | "in", month] => (ordinal, weekday, month),
+            _ => return None,
+        };
+        let nth = match ordinal.to_ascii_lowercase().as_str() {
+            "first" | "1st" => 1,
+            "second" | "2nd" => 2,
+            "third" | "3rd" => 3,
+            "fourth" | "4th" => 4,
+            "fifth" | "5th" => 5,
+            "last" => -1,
+            _ => return None,
+        };
+        let weekday = Weekday::ALL
+            .into_iter()
+            .find(|day| day.to_string().eq_ignore_ascii_case(weekday))?;
+        let month = Month::ALL
+            .into_iter()
+            .find(|name| name.to_string().eq_ignore_ascii_case(month))?;
+        Some(HolidayRule::NthWeekday(nth, weekday, month))
+    }
+}
+
+impl Holiday {
+    /// Read a text or ICS file, depending on its extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Holiday>, HolidayError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension == "ics") {
+            Holiday::parse_ics(&text)
+        } else {
+            Holiday::parse_list(&text)
+        }
+    }
+
+    /// Parse the text format, with a rule and an optional name on each line.
+    pub fn parse_list(text: &str) -> Result<Vec<Holiday>, HolidayError> {
+        let mut holidays = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Dates with times have colons, so only split on a colon followed by a space.
+            let (rule, name) = match line.split_once(": ") {
+                Some((rule, name)) => (rule.trim(), name.trim()),
+                None => (line.trim_end_matches(':'), line.trim_end_matches(':')),
+            };
+            let rule = HolidayRule::parse(rule).ok_or(HolidayError::InvalidLine(index + 1))?;
+            holidays.push(Holiday {
+                name: name.to_string(),
+                rule,
+            });
+        }
+        Ok(holidays)
+    }
+
+    /// Parse the VEVENTs of an iCalendar file. All of the other components are skipped.
+    /// Times with a TZID are rejected rather than read as UTC, as the VTIMEZONEs aren't
+    /// read.
+    pub fn parse_ics(text: &str) -> Result<Vec<Holiday>, HolidayError> {
+        // Long lines are folded onto the next line, which starts with a space or a tab.
+        let mut lines: Vec<(usize, String)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+                (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+                _ => lines.push((index + 1, line.to_string())),
+            }
+        }
+
+        let mut holidays = Vec::new();
+        let mut event: Option<Event> = None;
+        for (line_number, line) in lines {
+            let invalid = || HolidayError::InvalidLine(line_number);
+            let (name, value) = match line.split_once(':') {
+                Some((name, value)) => (name, value),
+                None if line.trim().is_empty() => continue,
+                None => return Err(invalid()),
+            };
+            let (name, parameters) = name.split_once(';').unwrap_or((name, ""));
+            let has_zone = parameters
+                .split(';')
+                .any(|parameter| parameter.starts_with("TZID="));
+            match (name, &mut event) {
+                ("DTSTART" | "EXDATE", Some(_)) if has_zone => return Err(invalid()),
+                ("BEGIN", None) if value == "VEVENT" => event = Some(Event::default()),
+                ("END", Some(_)) if value == "VEVENT" => {
+                    let Event {
+                        start,
+                        rule,
+                        exceptions,
+                        summary,
+                    } = event.take().unwrap();
+                    let start = start.ok_or_else(invalid)?;
+                    let rule = match rule {
+                        Some(rule) => HolidayRule::Recurrence(
+                            Recurrence {
+                                start,
+                                rule,
+                                exceptions,
+                            },
+                            OccurrenceCache::default(),
+                        ),
+                        None => HolidayRule::Date(start.year, start.month, start.day),
+                    };
+                    holidays.push(Holiday {
+                        name: summary,
+                        rule,
+                    });
+                }
+                ("DTSTART", Some(event)) => {
+                    event.start = Some(value.parse().map_err(|_| invalid())?)
+                }
+                ("RRULE", Some(event)) => event.rule = Some(value.parse().map_err(|_| invalid())?),
+                ("EXDATE", Some(event)) => {
+                    for date in value.split(',') {
+                        let date: Date = date.parse().map_err(|_| invalid())?;
+                        event.exceptions.push(date.epoch());
+                    }
+                }
+                ("SUMMARY", Some(event)) => event.summary = unescape(value),
+                _ => {}
+            }
+        }
+        if event.is_some() {
+            return Err(HolidayError::InvalidLine(text.lines().count()));
+        }
+        Ok(holidays)
+    }
+}
+
+/// The properties of a VEVENT that is being parsed.
+#[derive(Default)]
+struct Event {
+    start: Option<Date>,
+    rule: Option<RecurrenceRule>,
+    exceptions: Vec<EpochMS>,
+    summary: String,
+}
+
+/// Undo the escaping of iCalendar text values.
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            text.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusinessCalendar {
+    weekend: Vec<Weekday>,
+    holidays: Vec<Holiday>,
+}
+
+impl Default for BusinessCalendar {
+    /// A Saturday and Sunday weekend, without any holidays.
+    fn default() -> BusinessCalendar {
+        BusinessCalendar::new(&[Weekday::Saturday, Weekday::Sunday])
+    }
+}
+
+impl BusinessCalendar {
+    /// Panics if every day of the week is on the weekend.
+    pub fn new(weekend: &[Weekday]) -> BusinessCalendar {
+        assert!(
+            Weekday::ALL.iter().any(|day| !weekend.contains(day)),
+            "At least one day of the week must be a business day."
+        );
+        BusinessCalendar {
+            weekend: weekend.to_vec(),
+            holidays: Vec::new(),
+        }
+    }
+
+    pub fn with_holidays(mut self, holidays: impl IntoIterator<Item = Holiday>) -> Self {
+        self.holidays.extend(holidays);
+        self
+    }
+
+    pub fn is_weekend(&self, date: &Date) -> bool {
+        self.weekend.contains(&date.weekday)
+    }
+
+    pub fn holiday(&self, date: &Date) -> Option<&Holiday> {
+        self.holidays
+            .iter()
+            .find(|holiday| holiday.rule.occurs_on(date))
+    }
+
+    pub fn is_business_day(&self, date: &Date) -> bool {
+        !self.is_weekend(date) && self.holiday(date).is_none()
+    }
+
+    /// The first business day after the date, with the same time of day.
+    pub fn next_business_day(&self, date: &Date) -> Option<Date> {
+        self.add_business_days(date, 1)
+    }
+
+    /// The last business day before the date, with the same time of day.
+    pub fn previous_business_day(&self, date: &Date) -> Option<Date> {
+        self.add_business_days(date, -1)
+    }
+
+    /// Move forward, or backward when negative, by a number of business days. The date
+    /// itself doesn't need to be a business day, and is returned as is for 0 days. `None`
+    /// when a year goes by without a business day, as when the holidays cover every
+    /// weekday, or when it runs out of timestamps.
+    pub fn add_business_days(&self, date: &Date, days: i64) -> Option<Date> {
+        let step = days.signum() * 86_400_000;
+        let mut remaining = days.unsigned_abs();
+        let mut date = date.clone();
+        let mut days_without = 0;
+        while remaining > 0 {
+            date = Date::from(EpochMS(date.epoch().0.checked_add(step)?));
+            if self.is_business_day(&date) {
+                remaining -= 1;
+                days_without = 0;
+            } else {
+                days_without += 1;
+                if days_without > 366 {
+                    return None;
+                }
+            }
+        }
+        Some(date)
+    }
+
+    /// The business days from the start up to but not including the end, ignoring the time
+    /// of day. It's negative when the end is before the start.
+    pub fn business_days_between(&self, start: &Date, end: &Date) -> i64 {
+        let (first, last) = (start.epoch().days_since(), end.epoch().days_since());
+        let count = |first: i64, last: i64| {
+            (first..last)
+                .filter(|days| self.is_business_day(&Date::from(EpochMS(days * 86_400_000))))
+                .count() as i64
+        };
+        if first <= last {
+            count(first, last)
+        } else {
+            -count(last, first)
+        }
+    }
+}
+
+#[cfg(test)]
+fn date(year: i64, month: Month, day: u8) -> Date {
+    Date::new(year, month, day, 0, 0, 0, 0).unwrap()
+}
+
+#[test]
+fn test_holiday_rules() {
+    let holidays = Holiday::from_file("data/holidays/us-federal.txt").unwrap();
+    assert_eq!(holidays.len(), 11);
+    let calendar = BusinessCalendar::default().with_holidays(holidays);
+
+    let cases = [
+        (date(2021, Month::November, 25), Some("Thanksgiving Day")),
+        (date(2020, Month::November, 26), Some("Thanksgiving Day")),
+        (date(2021, Month::November, 18), None),
+        (date(2021, Month::May, 31), Some("Memorial Day")),
+        (date(2021, Month::May, 24), None),
+        (
+            date(2021, Month::January, 18),
+            Some("Birthday of Martin Luther King, Jr."),
+        ),
+        (date(2021, Month::December, 25), Some("Christmas Day")),
+    ];
+    for (date, name) in cases {
+        assert_eq!(
+            calendar.holiday(&date).map(|holiday| holiday.name.as_str()),
+            name,
+            "{}",
+            date
+        );
+    }
+
+    let holidays = Holiday::parse_list(
+        "2021-12-24: Christmas Eve
+        # A comment
+        2nd Tuesday in March
+        --02-29: Leap Day",
+    )
+    .unwrap();
+    assert_eq!(
+        holidays[0].rule,
+        HolidayRule::Date(2021, Month::December, 24)
+    );
+    assert_eq!(holidays[1].name, "2nd Tuesday in March");
+    assert_eq!(
+        holidays[1].rule,
+        HolidayRule::NthWeekday(2, Weekday::Tuesday, Month::March)
+    );
+    assert!(holidays[2].rule.occurs_on(&date(2024, Month::February, 29)));
+
+    assert!(matches!(
+        Holiday::parse_list("--01-01: New Year\nsixth Monday of May"),
+        Err(HolidayError::InvalidLine(2))
+    ));
+    assert!(matches!(
+        Holiday::parse_list("--02-30"),
+        Err(HolidayError::InvalidLine(1))
+    ));
+}
+
+#[test]
+fn test_ics_holidays() {
+    let holidays = Holiday::parse_ics(
+        "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20211126\r
+SUMMARY:Day after\r
+  Thanksgiving\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20000101\r
+RRULE:FREQ=YEARLY;BYMONTH=1;BYDAY=1MO\r
+EXDATE;VALUE=DATE:20210104\r
+SUMMARY:Company offsite\\, first Monday\r
+END:VEVENT\r
+END:VCALENDAR\r
+",
+    )
+    .unwrap();
+    assert_eq!(holidays.len(), 2);
+    assert_eq!(holidays[0].name, "Day after Thanksgiving");
+    assert_eq!(holidays[1].name, "Company offsite, first Monday");
+
+    let calendar = BusinessCalendar::default().with_holidays(holidays);
+    assert!(calendar.holiday(&date(2021, Month::November, 26)).is_some());
+    assert!(calendar.holiday(&date(2020, Month::January, 6)).is_some());
+    assert!(calendar.holiday(&date(2020, Month::January, 13)).is_none());
+    assert!(calendar.holiday(&date(2021, Month::January, 4)).is_none());
+    // The cached years can be asked about again, in any order.
+    assert!(calendar.holiday(&date(2030, Month::January, 7)).is_some());
+    assert!(calendar.holiday(&date(2020, Month::January, 6)).is_some());
+    assert!(calendar.holiday(&date(1999, Month::January, 4)).is_none());
+    let offsites = (2000..2030)
+        .flat_map(|year| (1..=7).map(move |day| date(year, Month::January, day)))
+        .filter(|date| calendar.holiday(date).is_some())
+        .count();
+    assert_eq!(offsites, 29);
+
+    assert!(matches!(
+        Holiday::parse_ics("BEGIN:VEVENT\nSUMMARY:No start\nEND:VEVENT"),
+        Err(HolidayError::InvalidLine(3))
+    ));
+    assert!(matches!(
+        Holiday::parse_ics(
+            "BEGIN:VEVENT\nDTSTART;TZID=America/New_York:20211126T090000\nEND:VEVENT"
+        ),
+        Err(HolidayError::InvalidLine(2))
+    ));
+}
+
+#[test]
+fn test_business_days() {
+    let holidays = Holiday::from_file("data/holidays/us-federal.txt").unwrap();
+    let calendar = BusinessCalendar::default().with_holidays(holidays);
+
+    // Thanksgiving is on Thursday, so Wednesday is followed by Friday.
+    let wednesday = Date::new(2021, Month::November, 24, 17, 0, 0, 0).unwrap();
+    assert_eq!(
+        calendar.next_business_day(&wednesday),
+        Some(Date::new(2021, Month::November, 26, 17, 0, 0, 0).unwrap())
+    );
+    // Friday is followed by Monday.
+    let friday = date(2021, Month::November, 26);
+    assert_eq!(
+        calendar.next_business_day(&friday),
+        Some(date(2021, Month::November, 29))
+    );
+    assert_eq!(
+        calendar.previous_business_day(&friday),
+        Some(date(2021, Month::November, 24))
+    );
+    assert_eq!(
+        calendar.add_business_days(&date(2021, Month::December, 20), 5),
+        Some(date(2021, Month::December, 27))
+    );
+    assert_eq!(
+        calendar.add_business_days(&date(2021, Month::December, 27), -5),
+        Some(date(2021, Month::December, 20))
+    );
+
+    // November 2021 has 22 weekdays, and two holidays.
+    let november = date(2021, Month::November, 1);
+    let december = date(2021, Month::December, 1);
+    assert_eq!(calendar.business_days_between(&november, &december), 20);
+    assert_eq!(calendar.business_days_between(&december, &november), -20);
+    assert_eq!(
+        BusinessCalendar::default().business_days_between(&november, &december),
+        22
+    );
+
+    // A Friday and Saturday weekend.
+    let calendar = BusinessCalendar::new(&[Weekday::Friday, Weekday::Saturday]);
+    assert!(calendar.is_weekend(&friday));
+    assert_eq!(
+        calendar.next_business_day(&date(2021, Month::November, 25)),
+        Some(date(2021, Month::November, 28))
+    );
+
+    // Holidays can leave no business days at all, and the timestamps can run out.
+    let every_day = Holiday::parse_ics(
+        "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20000101\nRRULE:FREQ=DAILY\nSUMMARY:Closed\nEND:VEVENT",
+    )
+    .unwrap();
+    let closed = BusinessCalendar::default().with_holidays(every_day);
+    assert_eq!(closed.next_business_day(&friday), None);
+    assert_eq!(
+        closed.previous_business_day(&date(2000, Month::March, 1)),
+        Some(date(1999, Month::December, 31))
+    );
+    let last = Date::from(EpochMS(i64::MAX));
+    assert_eq!(BusinessCalendar::default().next_business_day(&last), None);
+}
@@ -0,0 +1,450 @@
+//! Formatting dates with patterns, either strftime style like `%Y-%m-%d %H:%M`, or CLDR
+//! style like `EEEE, MMMM d`. A pattern is compiled once into a [DateFormat], which can then
+//! be reused to format any number of dates.
+//!
+//! Names come from the `Display` implementations of [Month](super::Month) and
+//! [Weekday](super::Weekday), and are abbreviated to their first three letters.
+
+use super::Date;
+use std::fmt;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormat {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// The full year, padded to at least 4 digits.
+    Year,
+    /// The last two digits of the year.
+    ShortYear,
+    /// The year with no padding.
+    YearUnpadded,
+    Month(Style),
+    Day(Number),
+    /// The day of the year, optionally padded to 3 digits.
+    DayOfYear {
+        padded: bool,
+    },
+    /// The ISO 8601 week-numbering year, padded to at least 4 digits.
+    IsoWeekYear,
+    IsoWeek(Number),
+    Weekday(Style),
+    /// Monday is 1 and Sunday is 7.
+    WeekdayFromMonday,
+    /// Sunday is 0 and Saturday is 6.
+    WeekdayFromSunday,
+    Hour(Number),
+    /// The hour on a 12 hour clock, 1 to 12.
+    Hour12(Number),
+    AmPm,
+    Minute(Number),
+    Second(Number),
+    Millisecond,
+    /// The offset from UTC, which is always `+0000` as dates are in UTC.
+    Offset,
+    /// The offset as `Z`, as in ISO 8601.
+    OffsetZ,
+    /// The time zone abbreviation, which is always `UTC`.
+    TimeZone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Number {
+    /// 5
+    Unpadded,
+    /// 05
+    ZeroPadded,
+    /// " 5"
+    SpacePadded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Numeric(Number),
+    /// Feb
+    Abbreviated,
+    /// February
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// A `%` directive that isn't supported.
+    UnknownDirective(char),
+    /// The pattern ended right after a `%`.
+    TrailingPercent,
+    /// A CLDR pattern letter, or a run of them, that isn't supported, e.g. `MMMMMM`.
+    UnknownField(char, usize),
+    /// A quoted literal in a CLDR pattern was never closed.
+    UnterminatedQuote,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::UnknownDirective(ch) => write!(f, "unknown directive %{}", ch),
+            PatternError::TrailingPercent => write!(f, "the pattern ends with a lone %"),
+            PatternError::UnknownField(ch, count) => {
+                write!(f, "unknown field {}", ch.to_string().repeat(*count))
+            }
+            PatternError::UnterminatedQuote => write!(f, "the quoted text is never closed"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl DateFormat {
+    /// Compile a strftime pattern like `%A, %B %e %Y`. Everything other than the `%`
+    /// directives is copied through as-is.
+    pub fn strftime(pattern: &str) -> Result<DateFormat, PatternError> {
+        let mut builder = Builder::default();
+        let mut chars = pattern.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                builder.literal(ch);
+                continue;
+            }
+            let directive = chars.next().ok_or(PatternError::TrailingPercent)?;
+            let field = match directive {
+                '%' => {
+                    builder.literal('%');
+                    continue;
+                }
+                'n' => {
+                    builder.literal('\n');
+                    continue;
+                }
+                't' => {
+                    builder.literal('\t');
+                    continue;
+                }
+                // %F and %T are shorthands for the ISO 8601 date and time.
+                'F' => {
+                    builder.field_then(Field::Year, '-');
+                    builder.field_then(Field::Month(Style::Numeric(Number::ZeroPadded)), '-');
+                    Field::Day(Number::ZeroPadded)
+                }
+                'T' => {
+                    builder.field_then(Field::Hour(Number::ZeroPadded), ':');
+                    builder.field_then(Field::Minute(Number::ZeroPadded), ':');
+                    Field::Second(Number::ZeroPadded)
+                }
+                'Y' => Field::Year,
+                'y' => Field::ShortYear,
+                'm' => Field::Month(Style::Numeric(Number::ZeroPadded)),
+                'B' => Field::Month(Style::Full),
+                'b' | 'h' => Field::Month(Style::Abbreviated),
+                'd' => Field::Day(Number::ZeroPadded),
+                'e' => Field::Day(Number::SpacePadded),
+                'j' => Field::DayOfYear { padded: true },
+                'G' => Field::IsoWeekYear,
+                'V' => Field::IsoWeek(Number::ZeroPadded),
+                'A' => Field::Weekday(Style::Full),
+                'a' => Field::Weekday(Style::Abbreviated),
+                'u' => Field::WeekdayFromMonday,
+                'w' => Field::WeekdayFromSunday,
+                'H' => Field::Hour(Number::ZeroPadded),
+                'k' => Field::Hour(Number::SpacePadded),
+                'I' => Field::Hour12(Number::ZeroPadded),
+                'l' => Field::Hour12(Number::SpacePadded),
+                'p' => Field::AmPm,
+                'M' => Field::Minute(Number::ZeroPadded),
+                'S' => Field::Second(Number::ZeroPadded),
+                'L' => Field::Millisecond,
+                'z' => Field::Offset,
+                'Z' => Field::TimeZone,
+                _ => return Err(PatternError::UnknownDirective(directive)),
+            };
+            builder.field(field);
+        }
+        Ok(builder.build())
+    }
+
+    /// Compile a CLDR pattern like `EEEE, MMMM d, y`. ASCII letters are fields, and the
+    /// number of times they repeat picks the width. Literal text can be quoted with `'`, and
+    /// `''` is a single quote.
+    pub fn cldr(pattern: &str) -> Result<DateFormat, PatternError> {
+        let mut builder = Builder::default();
+        let mut chars = pattern.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if ch == '\'' {
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    builder.literal('\'');
+                    continue;
+                }
+                loop {
+                    match chars.next() {
+                        None => return Err(PatternError::UnterminatedQuote),
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            builder.literal('\'');
+                        }
+                        Some('\'') => break,
+                        Some(ch) => builder.literal(ch),
+                    }
+                }
+                continue;
+            }
+            if !ch.is_ascii_alphabetic() {
+                builder.literal(ch);
+                continue;
+            }
+
+            let mut count = 1;
+            while chars.peek() == Some(&ch) {
+                chars.next();
+                count += 1;
+            }
+            let padded = |count| {
+                if count == 1 {
+                    Number::Unpadded
+                } else {
+                    Number::ZeroPadded
+                }
+            };
+            let field = match (ch, count) {
+                ('y', 1) => Field::YearUnpadded,
+                ('y', 2) => Field::ShortYear,
+                ('y', _) => Field::Year,
+                ('M', 1..=2) => Field::Month(Style::Numeric(padded(count))),
+                ('M', 3) => Field::Month(Style::Abbreviated),
+                ('M', 4) => Field::Month(Style::Full),
+                ('d', 1..=2) => Field::Day(padded(count)),
+                ('D', 1) => Field::DayOfYear { padded: false },
+                ('D', 3) => Field::DayOfYear { padded: true },
+                ('Y', 4) => Field::IsoWeekYear,
+                ('w', 1..=2) => Field::IsoWeek(padded(count)),
+                ('E', 1..=3) => Field::Weekday(Style::Abbreviated),
+                ('E', 4) => Field::Weekday(Style::Full),
+                ('H', 1..=2) => Field::Hour(padded(count)),
+                ('h', 1..=2) => Field::Hour12(padded(count)),
+                ('a', 1) => Field::AmPm,
+                ('m', 1..=2) => Field::Minute(padded(count)),
+                ('s', 1..=2) => Field::Second(padded(count)),
+                ('S', 3) => Field::Millisecond,
+                ('X', 1) => Field::OffsetZ,
+                ('Z', 1..=3) => Field::Offset,
+                ('z', 1..=3) => Field::TimeZone,
+                _ => return Err(PatternError::UnknownField(ch, count)),
+            };
+            builder.field(field);
+        }
+        Ok(builder.build())
+    }
+
+    pub fn format(&self, date: &Date) -> String {
+        let mut string = String::new();
+        self.write(date, &mut string)
+            .expect("Writing to a String can't fail.");
+        string
+    }
+
+    pub fn write(&self, date: &Date, w: &mut impl Write) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                Item::Literal(text) => w.write_str(text)?,
+                Item::Field(field) => write_field(*field, date, w)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Date {
+    /// Format the date with a strftime pattern. Prefer compiling a [DateFormat] when
+    /// formatting more than one date with the same pattern.
+    pub fn format_strftime(&self, pattern: &str) -> Result<String, PatternError> {
+        Ok(DateFormat::strftime(pattern)?.format(self))
+    }
+
+    /// Format the date with a CLDR pattern.
+    pub fn format_cldr(&self, pattern: &str) -> Result<String, PatternError> {
+        Ok(DateFormat::cldr(pattern)?.format(self))
+    }
+}
+
+/// Collects the items, merging neighboring literals.
+#[derive(Default)]
+struct Builder {
+    items: Vec<Item>,
+}
+
+impl Builder {
+    fn literal(&mut self, ch: char) {
+        match self.items.last_mut() {
+            Some(Item::Literal(text)) => text.push(ch),
+            _ => self.items.push(Item::Literal(ch.to_string())),
+        }
+    }
+
+    fn field(&mut self, field: Field) {
+        self.items.push(Item::Field(field));
+    }
+
+    fn field_then(&mut self, field: Field, separator: char) {
+        self.field(field);
+        self.literal(separator);
+    }
+
+    fn build(self) -> DateFormat {
+        DateFormat { items: self.items }
+    }
+}
+
+fn write_field(field: Field, date: &Date, w: &mut impl Write) -> fmt::Result {
+    match field {
+        Field::Year => write_year(date.year, w),
+        Field::ShortYear => write!(w, "{:02}", date.year.rem_euclid(100)),
+        Field::YearUnpadded => write!(w, "{}", date.year),
+        Field::Month(Style::Numeric(number)) => write_number(date.month.number(), number, w),
+        Field::Month(style) => write_name(&date.month, style, w),
+        Field::Day(number) => write_number(date.day, number, w),
+        Field::DayOfYear { padded: true } => write!(w, "{:03}", date.day_of_year()),
+        Field::DayOfYear { padded: false } => write!(w, "{}", date.day_of_year()),
+        Field::IsoWeekYear => write_year(date.iso_week().year, w),
+        Field::IsoWeek(number) => write_number(date.iso_week().week, number, w),
+        Field::Weekday(style) => write_name(&date.weekday, style, w),
+        Field::WeekdayFromMonday => write!(w, "{}", date.weekday.number_from_monday()),
+        Field::WeekdayFromSunday => write!(w, "{}", date.weekday.number_from_sunday()),
+        Field::Hour(number) => write_number(date.hour, number, w),
+        Field::Hour12(number) => {
+            let hour = match date.hour % 12 {
+                0 => 12,
+                hour => hour,
+            };
+            write_number(hour, number, w)
+        }
+        Field::AmPm => w.write_str(if date.hour < 12 { "AM" } else { "PM" }),
+        Field::Minute(number) => write_number(date.minute, number, w),
+        Field::Second(number) => write_number(date.second, number, w),
+        Field::Millisecond => write!(w, "{:03}", date.millisecond),
+        Field::Offset => w.write_str("+0000"),
+        Field::OffsetZ => w.write_str("Z"),
+        Field::TimeZone => w.write_str("UTC"),
+    }
+}
+
+fn write_year(year: i64, w: &mut impl Write) -> fmt::Result {
+    if year < 0 {
+        write!(w, "-{:04}", -year)
+    } else {
+        write!(w, "{:04}", year)
+    }
+}
+
+fn write_number(value: u8, number: Number, w: &mut impl Write) -> fmt::Result {
+    match number {
+        Number::Unpadded => write!(w, "{}", value),
+        Number::ZeroPadded => write!(w, "{:02}", value),
+        Number::SpacePadded => write!(w, "{:2}", value),
+    }
+}
+
+fn write_name(name: &impl fmt::Display, style: Style, w: &mut impl Write) -> fmt::Result {
+    let name = name.to_string();
+    match style {
+        Style::Abbreviated => w.write_str(&name[..3]),
+        _ => w.write_str(&name),
+    }
+}
+
+#[cfg(test)]
+use super::Month;
+
+#[test]
+fn test_strftime() {
+    let date = Date::new(2021, Month::February, 7, 17, 9, 5, 23).unwrap();
+    assert_eq!(date.format_strftime("%Y-%m-%d").unwrap(), "2021-02-07");
+    assert_eq!(
+        date.format_strftime("%F %T.%L").unwrap(),
+        "2021-02-07 17:09:05.023"
+    );
+    assert_eq!(
+        date.format_strftime("%A, %B %e, %y at %I:%M %p").unwrap(),
+        "Sunday, February  7, 21 at 05:09 PM"
+    );
+    assert_eq!(
+        date.format_strftime("%a %b %d %k %l").unwrap(),
+        "Sun Feb 07 17  5"
+    );
+    assert_eq!(
+        date.format_strftime("%u %w %z %Z 100%%").unwrap(),
+        "7 0 +0000 UTC 100%"
+    );
+    assert_eq!(
+        DateFormat::strftime("%Q"),
+        Err(PatternError::UnknownDirective('Q'))
+    );
+    assert_eq!(
+        DateFormat::strftime("%Y%"),
+        Err(PatternError::TrailingPercent)
+    );
+}
+
+#[test]
+fn test_cldr() {
+    let date = Date::new(2021, Month::February, 7, 0, 9, 5, 23).unwrap();
+    assert_eq!(
+        date.format_cldr("EEEE, MMMM d").unwrap(),
+        "Sunday, February 7"
+    );
+    assert_eq!(
+        date.format_cldr("E MMM dd yy, h:mm:ss.SSS a").unwrap(),
+        "Sun Feb 07 21, 12:09:05.023 AM"
+    );
+    assert_eq!(
+        date.format_cldr("yyyy-MM-dd'T'HH:mmX").unwrap(),
+        "2021-02-07T00:09Z"
+    );
+    assert_eq!(date.format_cldr("y M d H m s").unwrap(), "2021 2 7 0 9 5");
+    // A `%` is just a literal in a CLDR pattern.
+    assert_eq!(date.format_cldr("d%").unwrap(), "7%");
+    assert_eq!(
+        date.format_cldr("'It''s' EEEE, 'o''clock'").unwrap(),
+        "It's Sunday, o'clock"
+    );
+    assert_eq!(
+        DateFormat::cldr("MMMMMM"),
+        Err(PatternError::UnknownField('M', 6))
+    );
+    assert_eq!(
+        DateFormat::cldr("'oops"),
+        Err(PatternError::UnterminatedQuote)
+    );
+
+    let date = Date::new(-44, Month::March, 15, 0, 0, 0, 0).unwrap();
+    assert_eq!(date.format_cldr("yyyy y yy").unwrap(), "-0044 -44 56");
+}
+
+#[test]
+fn test_week_fields() {
+    let date = Date::new(2021, Month::January, 3, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        date.format_strftime("%G-W%V-%u %Y-%j").unwrap(),
+        "2020-W53-7 2021-003"
+    );
+    assert_eq!(
+        date.format_cldr("YYYY 'week' w, D DDD").unwrap(),
+        "2020 week 53, 3 003"
+    );
+}
+
+#[test]
+fn test_reusable_format() {
+    let format = DateFormat::strftime("%Y%m%d-%H%M%S.log").unwrap();
+    let first = Date::new(2021, Month::February, 1, 17, 19, 5, 0).unwrap();
+    let second = first.clone() + std::time::Duration::from_secs(90);
+    assert_eq!(format.format(&first), "20210201-171905.log");
+    assert_eq!(format.format(&second), "20210201-172035.log");
+}
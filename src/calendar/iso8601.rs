@@ -0,0 +1,408 @@
+//! Timestamps in the ISO 8601 and RFC 3339 formats, e.g. `2021-02-01T17:19:05.123Z`.
+//!
+//! RFC 3339 is a strict profile of ISO 8601 for internet protocols. It always has a full
+//! date, a full time, and an offset, and only supports the years 0000 to 9999. ISO 8601
+//! additionally allows reduced precision, the "basic" format without separators, and
+//! expanded years with a sign.
+
+use super::{epoch_from_fields, Date, DateError, EpochMS, Month};
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How much of the time to include in ISO 8601 output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precision {
+    /// 2021-02-01
+    Days,
+    /// 2021-02-01T17Z
+    Hours,
+    /// 2021-02-01T17:19Z
+    Minutes,
+    /// 2021-02-01T17:19:05Z
+    Seconds,
+    /// 2021-02-01T17:19:05.123Z
+    Milliseconds,
+}
+
+impl Date {
+    /// Format the date as an RFC 3339 timestamp in UTC, with millisecond precision. RFC 3339
+    /// only allows 4 digit years, so other years are an error.
+    pub fn to_rfc3339(&self) -> Result<String, DateError> {
+        if !(0..=9999).contains(&self.year) {
+            return Err(DateError::YearOutOfRange(self.year));
+        }
+        Ok(self.to_iso8601(Precision::Milliseconds))
+    }
+
+    /// Format the date in the ISO 8601 extended format in UTC. Years outside of 0000 to 9999
+    /// use the expanded representation with a sign and 6 digits, e.g. `-000044-03-15`.
+    pub fn to_iso8601(&self, precision: Precision) -> String {
+        let mut string = String::new();
+        if (0..=9999).contains(&self.year) {
+            write!(string, "{:04}", self.year).unwrap();
+        } else {
+            write!(string, "{:+07}", self.year).unwrap();
+        }
+        write!(string, "-{:02}-{:02}", self.month.number(), self.day).unwrap();
+        if precision == Precision::Days {
+            return string;
+        }
+
+        write!(string, "T{:02}", self.hour).unwrap();
+        if precision >= Precision::Minutes {
+            write!(string, ":{:02}", self.minute).unwrap();
+        }
+        if precision >= Precision::Seconds {
+            write!(string, ":{:02}", self.second).unwrap();
+        }
+        if precision >= Precision::Milliseconds {
+            write!(string, ".{:03}", self.millisecond).unwrap();
+        }
+        string.push('Z');
+        string
+    }
+}
+
+/// Parses both RFC 3339 and the common ISO 8601 date and time forms. The date is converted
+/// to UTC using the offset. A timestamp without an offset is treated as already being UTC.
+impl FromStr for Date {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Date, ParseError> {
+        Parser::new(text).parse()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The text ended while more was expected.
+    UnexpectedEnd,
+    /// A character that doesn't fit the format, at a byte index.
+    UnexpectedCharacter(usize, char),
+    /// The offset hours or minutes were out of range.
+    InvalidOffset,
+    /// All of the fields parsed, but they don't form a real date, e.g. February 30.
+    InvalidDate(DateError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedEnd => write!(f, "the timestamp ended unexpectedly"),
+            ParseError::UnexpectedCharacter(index, ch) => {
+                write!(f, "unexpected character {:?} at index {}", ch, index)
+            }
+            ParseError::InvalidOffset => write!(f, "the UTC offset is out of range"),
+            ParseError::InvalidDate(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<DateError> for ParseError {
+    fn from(error: DateError) -> ParseError {
+        ParseError::InvalidDate(error)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser { text, index: 0 }
+    }
+
+    fn parse(&mut self) -> Result<Date, ParseError> {
+        let year = self.year()?;
+        // The extended format separates the fields, e.g. 2021-02-01T17:19:05 rather than
+        // 20210201T171905. Whichever is used for the date is also expected for the time.
+        let extended = self.eat(b'-');
+        let month = Month::try_from(self.digits(2)? as u8)?;
+        if extended {
+            self.expect(b'-')?;
+        }
+        let day = self.digits(2)? as u8;
+
+        let (mut hour, mut minute, mut second, mut millisecond) = (0, 0, 0, 0);
+        let mut offset_minutes = 0;
+        if self.peek().is_some() {
+            match self.peek() {
+                Some(b'T') | Some(b't') | Some(b' ') => self.index += 1,
+                _ => return Err(self.error()),
+            }
+            hour = self.digits(2)? as u8;
+            if self.time_separator(extended)? {
+                minute = self.digits(2)? as u8;
+                if self.time_separator(extended)? {
+                    second = self.digits(2)? as u8;
+                    if self.eat(b'.') || self.eat(b',') {
+                        millisecond = self.fraction()?;
+                    }
+                }
+            }
+            offset_minutes = self.offset()?;
+        }
+
+        if self.peek().is_some() {
+            return Err(self.error());
+        }
+
+        // A leap second is 23:59:60 in UTC, which can be at any hour and minute locally, so
+        // it is only checked once the offset has been applied.
+        let is_leap_second = second == 60;
+        if is_leap_second {
+            second = 59;
+        }
+        let local = epoch_from_fields(year, month, day, hour, minute, second, millisecond)?;
+        let date = Date::from(EpochMS(local.0 - offset_minutes * 60_000));
+        if !is_leap_second {
+            return Ok(date);
+        }
+        if (date.hour, date.minute) != (23, 59) {
+            return Err(DateError::InvalidSecond(60).into());
+        }
+        Ok(date.into_leap_second())
+    }
+
+    /// A 4 digit year, or an expanded year with a sign and at least 4 digits.
+    fn year(&mut self) -> Result<i64, ParseError> {
+        let sign = match self.peek() {
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Ok(self.digits(4)? as i64),
+        };
+        self.index += 1;
+        let start = self.index;
+        let mut year: i64 = 0;
+        // Stop well before the year could overflow, any extra digits are then unexpected.
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            if self.index - start == 18 {
+                break;
+            }
+            year = year * 10 + (digit - b'0') as i64;
+            self.index += 1;
+        }
+        if self.index - start < 4 {
+            return Err(self.error());
+        }
+        Ok(sign * year)
+    }
+
+    /// Returns whether another time component follows. In the extended format it's
+    /// introduced with a colon, and in the basic format it's just more digits.
+    fn time_separator(&mut self, extended: bool) -> Result<bool, ParseError> {
+        if extended {
+            Ok(self.eat(b':'))
+        } else {
+            Ok(matches!(self.peek(), Some(b'0'..=b'9')))
+        }
+    }
+
+    /// Any number of fractional digits, truncated to milliseconds.
+    fn fraction(&mut self) -> Result<u16, ParseError> {
+        let start = self.index;
+        let mut millisecond = 0;
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            if self.index - start < 3 {
+                millisecond = millisecond * 10 + (digit - b'0') as u16;
+            }
+            self.index += 1;
+        }
+        match self.index - start {
+            0 => Err(self.error()),
+            1 => Ok(millisecond * 100),
+            2 => Ok(millisecond * 10),
+            _ => Ok(millisecond),
+        }
+    }
+
+    /// `Z`, or a numeric offset like `+05:30`, `+0530` or `+05`, in minutes east of UTC. The
+    /// colon is optional in either format, as plenty of systems leave it out.
+    fn offset(&mut self) -> Result<i64, ParseError> {
+        let sign = match self.peek() {
+            None => return Ok(0),
+            Some(b'Z') | Some(b'z') => {
+                self.index += 1;
+                return Ok(0);
+            }
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Err(self.error()),
+        };
+        self.index += 1;
+        let hours = self.digits(2)?;
+        let minutes = if self.peek().is_none() {
+            0
+        } else {
+            self.eat(b':');
+            self.digits(2)?
+        };
+        if hours > 23 || minutes > 59 {
+            return Err(ParseError::InvalidOffset);
+        }
+        Ok(sign * (hours * 60 + minutes) as i64)
+    }
+
+    fn digits(&mut self, count: usize) -> Result<u32, ParseError> {
+        let mut value = 0;
+        for _ in 0..count {
+            match self.peek() {
+                Some(digit @ b'0'..=b'9') => {
+                    value = value * 10 + (digit - b'0') as u32;
+                    self.index += 1;
+                }
+                _ => return Err(self.error()),
+            }
+        }
+        Ok(value)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.index).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// The error for whatever is at the current index.
+    fn error(&self) -> ParseError {
+        match self.text[self.index..].chars().next() {
+            Some(ch) => ParseError::UnexpectedCharacter(self.index, ch),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+}
+
+#[test]
+fn test_to_rfc3339() {
+    let date = Date::from(EpochMS(1_612_199_945_123));
+    assert_eq!(date.to_rfc3339().unwrap(), "2021-02-01T17:19:05.123Z");
+
+    let date = Date::from(EpochMS(-1));
+    assert_eq!(date.to_rfc3339().unwrap(), "1969-12-31T23:59:59.999Z");
+
+    let date = Date::new(10_000, Month::January, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(date.to_rfc3339(), Err(DateError::YearOutOfRange(10_000)));
+}
+
+#[test]
+fn test_to_iso8601() {
+    let date = Date::from(EpochMS(1_612_199_945_123));
+    assert_eq!(date.to_iso8601(Precision::Days), "2021-02-01");
+    assert_eq!(date.to_iso8601(Precision::Hours), "2021-02-01T17Z");
+    assert_eq!(date.to_iso8601(Precision::Minutes), "2021-02-01T17:19Z");
+    assert_eq!(date.to_iso8601(Precision::Seconds), "2021-02-01T17:19:05Z");
+    assert_eq!(
+        date.to_iso8601(Precision::Milliseconds),
+        "2021-02-01T17:19:05.123Z"
+    );
+
+    let date = Date::new(-44, Month::March, 15, 0, 0, 0, 0).unwrap();
+    assert_eq!(date.to_iso8601(Precision::Days), "-000044-03-15");
+    let date = Date::new(12_021, Month::March, 15, 0, 0, 0, 0).unwrap();
+    assert_eq!(date.to_iso8601(Precision::Days), "+012021-03-15");
+}
+
+#[test]
+fn test_parse() {
+    let expected = Date::from(EpochMS(1_612_199_945_123));
+    for text in [
+        "2021-02-01T17:19:05.123Z",
+        "2021-02-01t17:19:05.123z",
+        "2021-02-01 17:19:05.123Z",
+        "2021-02-01T17:19:05.123456789Z",
+        "2021-02-01T17:19:05,123Z",
+        "2021-02-01T12:19:05.123-05:00",
+        "2021-02-01T22:49:05.123+05:30",
+        "2021-02-01T22:49:05.123+0530",
+        "2021-02-02T02:19:05.123+09",
+        "2021-02-01T17:19:05.123-00:00",
+        "20210201T171905.123Z",
+        "20210201T121905.123-0500",
+    ] {
+        assert_eq!(
+            text.parse::<Date>(),
+            Ok(expected.clone()),
+            "Parses {}",
+            text
+        );
+    }
+
+    let midnight = Date::new(2021, Month::February, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!("2021-02-01".parse::<Date>(), Ok(midnight.clone()));
+    assert_eq!("20210201".parse::<Date>(), Ok(midnight.clone()));
+    assert_eq!("2021-02-01T00Z".parse::<Date>(), Ok(midnight.clone()));
+    assert_eq!("2021-02-01T00:00".parse::<Date>(), Ok(midnight));
+
+    let date = Date::new(-44, Month::March, 15, 0, 0, 0, 0).unwrap();
+    assert_eq!("-000044-03-15".parse::<Date>(), Ok(date.clone()));
+    assert_eq!(
+        date.to_iso8601(Precision::Seconds).parse::<Date>(),
+        Ok(date)
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!("".parse::<Date>(), Err(ParseError::UnexpectedEnd));
+    assert_eq!(
+        "+1234567890123456789-01-01".parse::<Date>(),
+        Err(ParseError::UnexpectedCharacter(20, '-'))
+    );
+    assert_eq!("2021-02".parse::<Date>(), Err(ParseError::UnexpectedEnd));
+    assert_eq!(
+        "2021-02-30T00:00:00Z".parse::<Date>(),
+        Err(ParseError::InvalidDate(DateError::InvalidDay {
+            year: 2021,
+            month: Month::February,
+            day: 30
+        }))
+    );
+    assert_eq!(
+        "2021-13-01".parse::<Date>(),
+        Err(ParseError::InvalidDate(DateError::InvalidMonth(13)))
+    );
+    assert_eq!(
+        "2021-02-01T25:00:00Z".parse::<Date>(),
+        Err(ParseError::InvalidDate(DateError::InvalidHour(25)))
+    );
+    assert_eq!(
+        "2021-02-01T00:00:00+24:00".parse::<Date>(),
+        Err(ParseError::InvalidOffset)
+    );
+    assert_eq!(
+        "2021-02-01T00:00:00Zjunk".parse::<Date>(),
+        Err(ParseError::UnexpectedCharacter(20, 'j'))
+    );
+    assert_eq!(
+        "2021-0201".parse::<Date>(),
+        Err(ParseError::UnexpectedCharacter(7, '0'))
+    );
+    assert_eq!(
+        "2021-02-01T00:00:00.Z".parse::<Date>(),
+        Err(ParseError::UnexpectedCharacter(20, 'Z'))
+    );
+    assert_eq!(
+        "2021-02-01T00:00:00é".parse::<Date>(),
+        Err(ParseError::UnexpectedCharacter(19, 'é'))
+    );
+}
@@ -0,0 +1,376 @@
+//! Leap seconds, and conversions between UTC and International Atomic Time (TAI).
+//!
+//! [EpochMS] is POSIX time, where every day is exactly 86,400,000 ms long. UTC days
+//! occasionally have a leap second at the end, 23:59:60, to stay in step with the Earth's
+//! rotation, while TAI keeps counting steadily. GPS time is TAI - 19 seconds. The table of
+//! leap seconds is opt-in, and is loaded from the IERS `leap-seconds.list` format, e.g.:
+//!
+//! ```text
+//! #@    3991593600
+//! 2272060800    10    # 1 Jan 1972
+//! 2287785600    11    # 1 Jul 1972
+//! ```
+//!
+//! The times are seconds since 1900 (the NTP epoch), and each line starts a new offset of
+//! TAI - UTC. The `#@` line is when the file expires.
+
+use super::{epoch_from_fields, Date, DateError, EpochMS};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Seconds from the NTP epoch of 1900 to 1970.
+const NTP_TO_UNIX_SECONDS: i64 = 2_208_988_800;
+
+/// The instant of a time in seconds since 1900, unless it is too far out for [EpochMS].
+fn from_ntp_seconds(ntp_seconds: i64) -> Option<EpochMS> {
+    ntp_seconds
+        .checked_sub(NTP_TO_UNIX_SECONDS)?
+        .checked_mul(1000)
+        .map(EpochMS)
+}
+
+/// A reading of the TAI clock, in milliseconds since it read 1970-01-01 00:00:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaiMS(pub i64);
+
+impl TaiMS {
+    /// GPS time started on January 6, 1980 with no leap seconds, when TAI - UTC was 19.
+    pub const fn to_gps(self) -> i64 {
+        self.0 - 19_000
+    }
+
+    pub const fn from_gps(gps_ms: i64) -> TaiMS {
+        TaiMS(gps_ms + 19_000)
+    }
+}
+
+#[derive(Debug)]
+pub enum LeapSecondsError {
+    Io(io::Error),
+    /// A line couldn't be parsed, with its line number starting at 1.
+    InvalidLine(usize),
+    /// The entries must be in order.
+    OutOfOrder(usize),
+    Empty,
+}
+
+impl fmt::Display for LeapSecondsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeapSecondsError::Io(error) => write!(f, "unable to read the leap seconds: {}", error),
+            LeapSecondsError::InvalidLine(line) => write!(f, "line {} is invalid", line),
+            LeapSecondsError::OutOfOrder(line) => write!(f, "line {} is out of order", line),
+            LeapSecondsError::Empty => write!(f, "there are no leap seconds in the list"),
+        }
+    }
+}
+
+impl std::error::Error for LeapSecondsError {}
+
+impl From<io::Error> for LeapSecondsError {
+    fn from(error: io::Error) -> LeapSecondsError {
+        LeapSecondsError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeapSeconds {
+    /// The UTC instant each offset starts, with the TAI - UTC seconds from then on.
+    entries: Vec<(EpochMS, i64)>,
+    expires: Option<EpochMS>,
+}
+
+impl LeapSeconds {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<LeapSeconds, LeapSecondsError> {
+        LeapSeconds::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parse the IERS format. The `#h` hash of the file isn't verified.
+    pub fn parse(text: &str) -> Result<LeapSeconds, LeapSecondsError> {
+        let mut entries: Vec<(EpochMS, i64)> = Vec::new();
+        let mut expires = None;
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let invalid = || LeapSecondsError::InvalidLine(line_number);
+            if let Some(expiration) = line.strip_prefix("#@") {
+                let ntp_seconds: i64 = expiration.trim().parse().map_err(|_| invalid())?;
+                expires = Some(from_ntp_seconds(ntp_seconds).ok_or_else(invalid)?);
+                continue;
+            }
+            // Drop the comments, which may also trail an entry.
+            let data = line.split('#').next().unwrap_or("").trim();
+            if data.is_empty() {
+                continue;
+            }
+            let mut fields = data.split_whitespace();
+            let ntp_seconds: i64 = fields
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+            let offset: i64 = fields
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+            let start = from_ntp_seconds(ntp_seconds).ok_or_else(invalid)?;
+            if matches!(entries.last(), Some(&(previous, _)) if previous >= start) {
+                return Err(LeapSecondsError::OutOfOrder(line_number));
+            }
+            entries.push((start, offset));
+        }
+        if entries.is_empty() {
+            return Err(LeapSecondsError::Empty);
+        }
+        Ok(LeapSeconds { entries, expires })
+    }
+
+    /// The list needs updating after this, as a new leap second may have been announced.
+    pub fn expires(&self) -> Option<EpochMS> {
+        self.expires
+    }
+
+    /// TAI - UTC in seconds at a UTC instant. Before 1972 the offset wasn't a whole number
+    /// of seconds, and the first entry of the list is used.
+    pub fn offset_at(&self, utc: EpochMS) -> i64 {
+        let next = self.entries.partition_point(|&(start, _)| start <= utc);
+        self.entries[next.saturating_sub(1)].1
+    }
+
+    pub fn utc_to_tai(&self, utc: EpochMS) -> TaiMS {
+        TaiMS(utc.0 + self.offset_at(utc) * 1000)
+    }
+
+    /// Convert TAI to POSIX time. POSIX time can't represent a leap second, so the whole
+    /// of a leap second maps to the start of the next day. Use [LeapSeconds::tai_to_date] to
+    /// get 23:59:60 instead.
+    pub fn tai_to_utc(&self, tai: TaiMS) -> EpochMS {
+        let (utc, leap_second_end) = self.tai_to_utc_parts(tai);
+        match leap_second_end {
+            Some(end) => end,
+            None => utc,
+        }
+    }
+
+    /// Convert TAI to a UTC date, which will have the second 60 during a leap second.
+    pub fn tai_to_date(&self, tai: TaiMS) -> Date {
+        match self.tai_to_utc_parts(tai) {
+            (utc, Some(_)) => {
+                // Show the leap second as the end of the previous day.
+                Date::from(EpochMS(utc.0 - 1000)).into_leap_second()
+            }
+            (utc, None) => Date::from(utc),
+        }
+    }
+
+    /// Convert a UTC date to TAI, where the second can be 60 on a day that ends with a
+    /// leap second.
+    pub fn date_to_tai(&self, date: &Date) -> Result<TaiMS, DateError> {
+        if date.second != 60 {
+            let utc = epoch_from_fields(
+                date.year,
+                date.month,
+                date.day,
+                date.hour,
+                date.minute,
+                date.second,
+                date.millisecond,
+            )?;
+            return Ok(self.utc_to_tai(utc));
+        }
+        // The leap second is the last second before midnight.
+        let end = epoch_from_fields(date.year, date.month, date.day, 23, 59, 59, 0)?.0 + 1000;
+        let is_leap_second = date.hour == 23
+            && date.minute == 59
+            && date.millisecond < 1000
+            && self.offset_at(EpochMS(end)) > self.offset_at(EpochMS(end - 1));
+        if !is_leap_second {
+            return Err(DateError::InvalidSecond(60));
+        }
+        let offset = self.offset_at(EpochMS(end - 1));
+        Ok(TaiMS(end + offset * 1000 + date.millisecond as i64))
+    }
+
+    /// The UTC instant, and when it's in a leap second, the end of that leap second.
+    fn tai_to_utc_parts(&self, tai: TaiMS) -> (EpochMS, Option<EpochMS>) {
+        // Find the entry by the TAI instant that it starts at.
+        let next = self
+            .entries
+            .partition_point(|&(start, offset)| start.0 + offset * 1000 <= tai.0);
+        let (_, offset) = self.entries[next.saturating_sub(1)];
+        let utc = EpochMS(tai.0 - offset * 1000);
+        // A positive leap second is where the UTC instant reaches the next entry's start
+        // before its TAI start does.
+        match self.entries.get(next) {
+            Some(&(start, _)) if next > 0 && utc >= start => (utc, Some(start)),
+            _ => (utc, None),
+        }
+    }
+}
+
+impl Date {
+    /// Turn 23:59:59 into the leap second 23:59:60. Its timestamp stays at the last
+    /// millisecond of the day, so that the days and weeks counted from it are still those of
+    /// the leap second's day. Converting it with [EpochMS::try_from] gives the start of the
+    /// next day instead, as that is where POSIX time waits during a leap second. Outside of
+    /// the calendar module a leap second is built with [Date::new], which checks the time.
+    pub(super) fn into_leap_second(mut self) -> Date {
+        debug_assert_eq!((self.hour, self.minute, self.second), (23, 59, 59));
+        self.second = 60;
+        self.timestamp += 999 - self.millisecond as i64;
+        self
+    }
+}
+
+#[cfg(test)]
+use super::Month;
+
+#[cfg(test)]
+fn get_leap_seconds() -> LeapSeconds {
+    LeapSeconds::from_file("data/leap-seconds.list").expect("Unable to load the leap seconds.")
+}
+
+#[test]
+fn test_parse() {
+    let leap_seconds = get_leap_seconds();
+    assert_eq!(leap_seconds.entries.len(), 28);
+    assert_eq!(
+        leap_seconds.entries[0],
+        (
+            Date::new(1972, Month::January, 1, 0, 0, 0, 0)
+                .unwrap()
+                .epoch(),
+            10
+        )
+    );
+    assert_eq!(
+        leap_seconds.expires().map(Date::from).unwrap().to_rfc3339(),
+        Ok("2026-06-28T00:00:00.000Z".into())
+    );
+
+    assert!(matches!(
+        LeapSeconds::parse("# Nothing here\n"),
+        Err(LeapSecondsError::Empty)
+    ));
+    assert!(matches!(
+        LeapSeconds::parse("2272060800 10\n2272060800 11\n"),
+        Err(LeapSecondsError::OutOfOrder(2))
+    ));
+    assert!(matches!(
+        LeapSeconds::parse("2272060800\tten\t# 1 Jan 1972\n"),
+        Err(LeapSecondsError::InvalidLine(1))
+    ));
+    assert!(matches!(
+        LeapSeconds::parse("2272060800 10\n#@ 9000000000000000000\n"),
+        Err(LeapSecondsError::InvalidLine(2))
+    ));
+    assert!(matches!(
+        LeapSeconds::parse("-9000000000000000000 10\n"),
+        Err(LeapSecondsError::InvalidLine(1))
+    ));
+}
+
+#[test]
+fn test_tai_utc_conversions() {
+    let leap_seconds = get_leap_seconds();
+    let date = Date::new(2021, Month::February, 1, 17, 19, 5, 123).unwrap();
+    let tai = leap_seconds.utc_to_tai(date.epoch());
+    assert_eq!(tai.0 - date.timestamp, 37_000);
+    assert_eq!(leap_seconds.tai_to_utc(tai), date.epoch());
+    assert_eq!(leap_seconds.date_to_tai(&date), Ok(tai));
+    assert_eq!(tai.to_gps() - date.timestamp, 18_000, "GPS is ahead by 18s");
+    assert_eq!(TaiMS::from_gps(tai.to_gps()), tai);
+
+    let date = Date::new(1980, Month::January, 6, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        leap_seconds.utc_to_tai(date.epoch()).to_gps(),
+        date.timestamp
+    );
+}
+
+#[test]
+fn test_second_60() {
+    let leap_seconds = get_leap_seconds();
+    // A leap second was added at the end of December 31, 2016.
+    let before = Date::new(2016, Month::December, 31, 23, 59, 59, 0).unwrap();
+    let after = Date::new(2017, Month::January, 1, 0, 0, 0, 0).unwrap();
+    let tai_before = leap_seconds.utc_to_tai(before.epoch());
+    let tai_after = leap_seconds.utc_to_tai(after.epoch());
+    assert_eq!(
+        tai_after.0 - tai_before.0,
+        2000,
+        "The minute had 61 seconds"
+    );
+
+    let leap = leap_seconds.tai_to_date(TaiMS(tai_before.0 + 1500));
+    assert_eq!(leap.to_string(), "Saturday, December 31, 2016 23:59:60:500");
+    assert_eq!(leap.to_rfc3339(), Ok("2016-12-31T23:59:60.500Z".into()));
+    // A leap second can be built and parsed back, even without the table.
+    assert_eq!(
+        "2016-12-31T23:59:60.500Z".parse::<Date>().as_ref(),
+        Ok(&leap)
+    );
+    assert_eq!(
+        "2016-12-31T18:59:60.500-05:00".parse::<Date>().as_ref(),
+        Ok(&leap)
+    );
+    assert_eq!(
+        Date::new(2016, Month::December, 31, 23, 59, 60, 500).as_ref(),
+        Ok(&leap)
+    );
+    assert_eq!(EpochMS::try_from(leap.clone()), Ok(after.epoch()));
+    assert_eq!(
+        Date::new(2016, Month::December, 31, 12, 0, 60, 0),
+        Err(DateError::InvalidSecond(60))
+    );
+    assert_eq!(
+        "2016-12-31T23:58:60Z".parse::<Date>(),
+        Err(DateError::InvalidSecond(60).into())
+    );
+    assert_eq!(
+        leap_seconds.date_to_tai(&leap),
+        Ok(TaiMS(tai_before.0 + 1500))
+    );
+    assert_eq!(
+        leap_seconds.tai_to_utc(TaiMS(tai_before.0 + 1500)),
+        after.epoch(),
+        "POSIX time waits at midnight during the leap second"
+    );
+    assert_eq!(
+        leap_seconds.tai_to_date(tai_before),
+        before,
+        "The second before is unaffected"
+    );
+    assert_eq!(
+        leap_seconds.tai_to_date(tai_after),
+        after,
+        "The second after is unaffected"
+    );
+
+    // Not every day has a leap second.
+    let mut date = Date::new(2021, Month::December, 31, 23, 59, 59, 0).unwrap();
+    date.second = 60;
+    assert_eq!(
+        leap_seconds.date_to_tai(&date),
+        Err(DateError::InvalidSecond(60))
+    );
+}
+
+#[test]
+fn test_leap_second_day() {
+    let before = Date::new(2016, Month::December, 31, 23, 59, 59, 0).unwrap();
+    let leap = Date::new(2016, Month::December, 31, 23, 59, 60, 500).unwrap();
+    let after = Date::new(2017, Month::January, 1, 0, 0, 0, 0).unwrap();
+    // The leap second is still on December 31, rather than the day it waits for.
+    assert_eq!(leap.day_of_year(), 366);
+    assert_eq!(before.days_until(&leap), 0);
+    assert_eq!(leap.days_until(&after), 1);
+    assert_eq!(leap.iso_week(), before.iso_week());
+    assert_eq!(leap.iso_week().to_string(), "2016-W52");
+    assert!(before.epoch() < leap.epoch() && leap.epoch() < after.epoch());
+}
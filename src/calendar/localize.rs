@@ -0,0 +1,146 @@
+//! Localized formatting of a [Date] with the ICU4X `DateTimeFormat`, which reads the CLDR
+//! data in `data/icu`. The date is handed to the formatter directly, rather than through a
+//! `MockDateTime`, so there is no string to format and parse along the way.
+
+use super::{Date, DateError};
+use icu::datetime::date::{self as icu_date, DateTimeType};
+use icu::datetime::DateTimeFormat;
+use icu::locid::LanguageIdentifier;
+use std::{fs, io, path::Path};
+
+/// ICU only has unsigned years, so use [Date::format_localized] for dates that could be
+/// before the year 1.
+impl DateTimeType for Date {
+    fn year(&self) -> usize {
+        self.year as usize
+    }
+
+    /// ICU months and days start at 0.
+    fn month(&self) -> icu_date::Month {
+        icu_date::Month::new_unchecked(self.month.number() - 1)
+    }
+
+    fn day(&self) -> icu_date::Day {
+        icu_date::Day::new_unchecked(self.day - 1)
+    }
+
+    fn hour(&self) -> icu_date::Hour {
+        icu_date::Hour::new_unchecked(self.hour)
+    }
+
+    fn minute(&self) -> icu_date::Minute {
+        icu_date::Minute::new_unchecked(self.minute)
+    }
+
+    fn second(&self) -> icu_date::Second {
+        icu_date::Second::new_unchecked(self.second)
+    }
+}
+
+impl Date {
+    /// Format the date with the pattern and the names of the formatter's locale. The date is
+    /// in UTC, and ICU can't format the years before 1.
+    pub fn format_localized(&self, formatter: &DateTimeFormat) -> Result<String, DateError> {
+        if self.year < 1 {
+            return Err(DateError::YearOutOfRange(self.year));
+        }
+        Ok(formatter.format_to_string(self))
+    }
+}
+
+/// The locales that have date data in an ICU data directory such as `data/icu`.
+pub fn available_locales(data_dir: &Path) -> io::Result<Vec<LanguageIdentifier>> {
+    let mut locales: Vec<LanguageIdentifier> = Vec::new();
+    for entry in fs::read_dir(data_dir.join("dates/gregory@1"))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let locale = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok());
+        if let Some(locale) = locale {
+            locales.push(locale);
+        }
+    }
+    locales.sort_by_cached_key(|locale| locale.to_string());
+    Ok(locales)
+}
+
+#[cfg(test)]
+fn get_formatter(
+    locale: &str,
+    options: &icu::datetime::DateTimeFormatOptions,
+) -> DateTimeFormat<'static> {
+    let provider = icu_provider_fs::FsDataProvider::try_new("data/icu")
+        .expect("Unable to find a provider at that directory.");
+    DateTimeFormat::try_new(locale.parse().unwrap(), &provider, options)
+        .expect("Failed to create a DateTimeFormat")
+}
+
+#[test]
+fn test_format_localized() {
+    use super::Month;
+    use icu::datetime::options::style;
+
+    let date = Date::new(2020, Month::October, 14, 13, 21, 50, 0).unwrap();
+    let formatter = get_formatter("en", &Default::default());
+    assert_eq!(
+        date.format_localized(&formatter).unwrap(),
+        "October 14, 2020 at 1:21:50 PM z"
+    );
+
+    let options = style::Bag {
+        date: Some(style::Date::Full),
+        time: Some(style::Time::Short),
+        ..Default::default()
+    }
+    .into();
+    let cases = [
+        ("en", "Wednesday, October 14, 2020 at 1:21 PM"),
+        ("de", "Mittwoch, 14. Oktober 2020 um 13:21"),
+        ("fr", "mercredi 14 octobre 2020 à 13:21"),
+        ("es", "miércoles, 14 de octubre de 2020, 13:21"),
+    ];
+    for (locale, expected) in cases {
+        let formatter = get_formatter(locale, &options);
+        assert_eq!(date.format_localized(&formatter).unwrap(), expected);
+    }
+
+    let date = Date::new(0, Month::January, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        date.format_localized(&formatter),
+        Err(DateError::YearOutOfRange(0))
+    );
+}
+
+#[test]
+fn test_format_every_locale() {
+    use super::Month;
+    use icu::datetime::date::MockDateTime;
+    use icu::datetime::options::style;
+
+    let locales = available_locales(Path::new("data/icu")).unwrap();
+    assert!(locales.len() > 500);
+    assert!(locales.iter().any(|locale| locale == "ja"));
+
+    // Every locale gives the same result as the mock date time.
+    let options = style::Bag {
+        date: Some(style::Date::Full),
+        time: Some(style::Time::Medium),
+        ..Default::default()
+    }
+    .into();
+    let date = Date::new(2021, Month::February, 1, 17, 19, 5, 123).unwrap();
+    let mock: MockDateTime = "2021-02-01T17:19:05".parse().unwrap();
+    for locale in locales {
+        let formatter = get_formatter(&locale.to_string(), &options);
+        assert_eq!(
+            date.format_localized(&formatter).unwrap(),
+            formatter.format_to_string(&mock),
+            "{}",
+            locale
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Spans of time between two timestamps, and iterators that step through them by calendar
+//! units, e.g. every day of a month, or every month of a year as its own span for bucketing
+//! events.
+
+use super::week::WeekRule;
+use super::{days_from_civil, Date, EpochMS, Month};
+
+/// The time from `start` up to, but not including, `end`. An inclusive span is stored with
+/// its end moved forward by a millisecond, so both kinds can be compared and combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: EpochMS,
+    pub end: EpochMS,
+}
+
+/// How far to move for each step of a span. Months and years keep the day of the month of
+/// the start, clamped to the end of shorter months, so stepping monthly from January 31
+/// gives February 28 and then March 31.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+}
+
+impl Step {
+    /// The date after a number of steps from the start, or None when it's out of range.
+    fn nth_from(&self, start: &Date, n: i64) -> Option<Date> {
+        let days = |days: i64| {
+            let ms = days.checked_mul(86_400_000)?;
+            Some(Date::from(EpochMS(start.epoch().0.checked_add(ms)?)))
+        };
+        match *self {
+            Step::Days(count) => days(n.checked_mul(count as i64)?),
+            Step::Weeks(count) => days(n.checked_mul(count as i64 * 7)?),
+            Step::Months(count) => start.add_months(n.checked_mul(count as i64)?).ok(),
+            Step::Years(count) => start.add_years(n.checked_mul(count as i64)?).ok(),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(
+            *self,
+            Step::Days(0) | Step::Weeks(0) | Step::Months(0) | Step::Years(0)
+        )
+    }
+}
+
+impl Span {
+    /// From `start` up to but not including `end`.
+    pub const fn new(start: EpochMS, end: EpochMS) -> Span {
+        Span { start, end }
+    }
+
+    /// From `start` up to and including `end`. The very last millisecond can't be included,
+    /// as there is no timestamp after it.
+    pub const fn inclusive(start: EpochMS, end: EpochMS) -> Span {
+        Span {
+            start,
+            end: EpochMS(end.0.saturating_add(1)),
+        }
+    }
+
+    /// The calendar day that the date is in, from midnight to midnight.
+    pub fn day(date: &Date) -> Span {
+        let start = date.epoch().days_since();
+        Span::new(
+            EpochMS(start * 86_400_000),
+            EpochMS((start + 1) * 86_400_000),
+        )
+    }
+
+    /// The week that the date is in, starting on the first day of the week of the rule.
+    pub fn week(date: &Date, rule: WeekRule) -> Span {
+        let days = date.epoch().days_since();
+        let days_into_week = (date.weekday.number_from_sunday() as i64
+            - rule.first_day.number_from_sunday() as i64)
+            .rem_euclid(7);
+        let start = days - days_into_week;
+        Span::new(
+            EpochMS(start * 86_400_000),
+            EpochMS((start + 7) * 86_400_000),
+        )
+    }
+
+    pub fn month(date: &Date) -> Span {
+        let start = days_from_civil(date.year, date.month, 1);
+        let end = start + date.month.days(date.is_leap_year) as i64;
+        Span::new(EpochMS(start * 86_400_000), EpochMS(end * 86_400_000))
+    }
+
+    pub fn year(date: &Date) -> Span {
+        let start = days_from_civil(date.year, Month::January, 1);
+        let end = days_from_civil(date.year + 1, Month::January, 1);
+        Span::new(EpochMS(start * 86_400_000), EpochMS(end * 86_400_000))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    pub fn contains(&self, epoch: EpochMS) -> bool {
+        self.start <= epoch && epoch < self.end
+    }
+
+    /// Whether the spans share any time. Spans that only touch, where one ends as the
+    /// other starts, don't overlap.
+    pub fn overlaps(&self, other: &Span) -> bool {
+        self.start < other.end && other.start < self.end && !self.is_empty() && !other.is_empty()
+    }
+
+    pub fn intersection(&self, other: &Span) -> Option<Span> {
+        let span = Span::new(self.start.max(other.start), self.end.min(other.end));
+        if span.is_empty() {
+            None
+        } else {
+            Some(span)
+        }
+    }
+
+    /// The dates from the start of the span, a step apart, that are inside of the span.
+    /// Panics if the step is zero, as it would never leave the start.
+    pub fn steps(&self, step: Step) -> Steps {
+        assert!(!step.is_zero(), "The step must be more than 0.");
+        Steps {
+            start: Date::from(self.start),
+            end: self.end,
+            step,
+            index: 0,
+        }
+    }
+
+    /// The span split at every step from its start, where the last bucket is cut short at the
+    /// end of the span.
+    pub fn buckets(&self, step: Step) -> Buckets {
+        Buckets {
+            steps: self.steps(step),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Steps {
+    start: Date,
+    end: EpochMS,
+    step: Step,
+    index: i64,
+}
+
+impl Iterator for Steps {
+    type Item = Date;
+
+    fn next(&mut self) -> Option<Date> {
+        let date = self.step.nth_from(&self.start, self.index)?;
+        if date.epoch() >= self.end {
+            return None;
+        }
+        self.index += 1;
+        Some(date)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Buckets {
+    steps: Steps,
+}
+
+impl Iterator for Buckets {
+    type Item = Span;
+
+    fn next(&mut self) -> Option<Span> {
+        let start = self.steps.next()?.epoch();
+        let end = self
+            .steps
+            .step
+            .nth_from(&self.steps.start, self.steps.index)
+            .map_or(self.steps.end, |date| date.epoch().min(self.steps.end));
+        Some(Span::new(start, end))
+    }
+}
+
+#[cfg(test)]
+fn date(year: i64, month: Month, day: u8) -> Date {
+    Date::new(year, month, day, 0, 0, 0, 0).unwrap()
+}
+
+#[test]
+fn test_span_steps() {
+    let january = Span::new(
+        date(2021, Month::January, 1).epoch(),
+        date(2021, Month::February, 1).epoch(),
+    );
+    assert_eq!(january.steps(Step::Days(1)).count(), 31);
+    let days: Vec<_> = january.steps(Step::Days(10)).map(|date| date.day).collect();
+    assert_eq!(days, [1, 11, 21, 31]);
+    let mondays: Vec<_> = Span::new(
+        date(2021, Month::February, 1).epoch(),
+        date(2021, Month::March, 1).epoch(),
+    )
+    .steps(Step::Weeks(1))
+    .map(|date| date.day)
+    .collect();
+    assert_eq!(mondays, [1, 8, 15, 22]);
+
+    // Inclusive spans include the end, and months clamp to shorter months.
+    let span = Span::inclusive(
+        date(2020, Month::January, 31).epoch(),
+        date(2020, Month::May, 31).epoch(),
+    );
+    let months: Vec<_> = span
+        .steps(Step::Months(1))
+        .map(|date| (date.month, date.day))
+        .collect();
+    assert_eq!(
+        months,
+        [
+            (Month::January, 31),
+            (Month::February, 29),
+            (Month::March, 31),
+            (Month::April, 30),
+            (Month::May, 31)
+        ]
+    );
+    let exclusive = Span::new(span.start, date(2020, Month::May, 31).epoch());
+    assert_eq!(exclusive.steps(Step::Months(1)).count(), 4);
+
+    let leap_days: Vec<_> = Span::new(
+        date(2000, Month::February, 29).epoch(),
+        date(2009, Month::January, 1).epoch(),
+    )
+    .steps(Step::Years(4))
+    .map(|date| (date.year, date.day))
+    .collect();
+    assert_eq!(leap_days, [(2000, 29), (2004, 29), (2008, 29)]);
+}
+
+#[test]
+fn test_span_buckets() {
+    let span = Span::new(
+        date(2021, Month::January, 15).epoch(),
+        date(2021, Month::April, 1).epoch(),
+    );
+    let buckets: Vec<_> = span.buckets(Step::Months(1)).collect();
+    assert_eq!(
+        buckets,
+        [
+            Span::new(
+                date(2021, Month::January, 15).epoch(),
+                date(2021, Month::February, 15).epoch()
+            ),
+            Span::new(
+                date(2021, Month::February, 15).epoch(),
+                date(2021, Month::March, 15).epoch()
+            ),
+            Span::new(
+                date(2021, Month::March, 15).epoch(),
+                date(2021, Month::April, 1).epoch()
+            ),
+        ]
+    );
+
+    // Bucketing events into calendar months.
+    let event = Date::new(2021, Month::February, 14, 18, 30, 0, 0).unwrap();
+    let month = Span::month(&event);
+    assert_eq!(
+        month,
+        Span::new(
+            date(2021, Month::February, 1).epoch(),
+            date(2021, Month::March, 1).epoch()
+        )
+    );
+    assert!(month.contains(event.epoch()));
+    assert!(!month.contains(month.end));
+    assert_eq!(
+        Span::year(&event).buckets(Step::Months(1)).nth(1),
+        Some(month)
+    );
+    assert_eq!(
+        Span::day(&event).start,
+        date(2021, Month::February, 14).epoch()
+    );
+    assert_eq!(
+        Span::week(&event, WeekRule::ISO).start,
+        date(2021, Month::February, 8).epoch()
+    );
+    assert_eq!(
+        Span::week(&event, WeekRule::US).start,
+        date(2021, Month::February, 14).epoch()
+    );
+    let year = Span::year(&date(super::MAX_YEAR, Month::June, 1));
+    assert_eq!(year.buckets(Step::Months(1)).count(), 12);
+
+    // The steps stop at the last timestamp, rather than overflowing.
+    let end = Span::inclusive(EpochMS(i64::MAX - 2 * 86_400_000), EpochMS(i64::MAX));
+    assert_eq!(end.end, EpochMS(i64::MAX));
+    assert_eq!(end.steps(Step::Days(1)).count(), 2);
+    assert_eq!(end.buckets(Step::Weeks(1)).count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "The step must be more than 0.")]
+fn test_zero_step() {
+    let span = Span::new(
+        date(2021, Month::March, 1).epoch(),
+        date(2021, Month::April, 1).epoch(),
+    );
+    span.buckets(Step::Months(0));
+}
+
+#[test]
+fn test_span_overlap() {
+    let day = |day| date(2021, Month::March, day).epoch();
+    let first = Span::new(day(1), day(10));
+    let second = Span::new(day(5), day(15));
+    let touching = Span::new(day(10), day(20));
+    assert!(first.overlaps(&second));
+    assert!(!first.overlaps(&touching));
+    assert_eq!(
+        first.intersection(&second),
+        Some(Span::new(day(5), day(10)))
+    );
+    assert_eq!(first.intersection(&touching), None);
+
+    // The inclusive end is one millisecond into the next span.
+    let inclusive = Span::inclusive(day(1), day(10));
+    assert!(inclusive.contains(day(10)));
+    assert!(inclusive.overlaps(&touching));
+    assert_eq!(
+        inclusive.intersection(&touching),
+        Some(Span::inclusive(day(10), day(10)))
+    );
+    assert!(Span::new(day(5), day(5)).is_empty());
+    assert!(!Span::new(day(5), day(5)).overlaps(&first));
+}
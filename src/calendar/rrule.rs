@@ -0,0 +1,1054 @@
+//! Recurrence rules from iCalendar (RFC 5545), e.g. `FREQ=MONTHLY;BYDAY=-1FR;COUNT=6` for
+//! the last Friday of the month, 6 times. A rule expands lazily into its occurrences,
+//! starting from a date. The supported parts are FREQ, INTERVAL, COUNT, UNTIL, BYMONTH,
+//! BYMONTHDAY, BYDAY, BYSETPOS and WKST.
+//!
+//! All of the times are in UTC, or are treated as floating times in no particular zone, so
+//! the `TZID` parameter isn't supported.
+
+use super::iso8601::ParseError;
+use super::systems::{Calendar, Gregorian};
+use super::{civil_from_days, days_from_civil, Date, EpochMS, Month, Weekday, MAX_YEAR};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Frequency {
+    Secondly,
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A weekday in BYDAY, such as `MO`, `2TU` for the second Tuesday of the month or year, or
+/// `-1FR` for the last Friday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    pub ordinal: Option<i8>,
+    pub weekday: Weekday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    /// How many periods of the frequency between each set of occurrences, starting at 1.
+    pub interval: u32,
+    pub count: Option<u32>,
+    /// The last possible occurrence, inclusive.
+    pub until: Option<EpochMS>,
+    pub by_month: Vec<Month>,
+    /// Days of the month, where negative days count back from the end of the month.
+    pub by_month_day: Vec<i8>,
+    pub by_day: Vec<ByDay>,
+    /// Picks occurrences out of the set of each period, starting at 1, or from the end when
+    /// negative.
+    pub by_set_pos: Vec<i16>,
+    pub week_start: Weekday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    MissingFrequency,
+    /// A rule part or property that isn't supported, such as BYWEEKNO.
+    Unsupported(String),
+    /// The name of a part, and its value that couldn't be parsed.
+    InvalidValue(String, String),
+    /// Only one of COUNT and UNTIL is allowed.
+    CountAndUntil,
+    InvalidDate(ParseError),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::MissingFrequency => write!(f, "the rule has no FREQ"),
+            RuleError::Unsupported(name) => write!(f, "{} is not supported", name),
+            RuleError::InvalidValue(name, value) => {
+                write!(f, "{:?} is not a valid value for {}", value, name)
+            }
+            RuleError::CountAndUntil => write!(f, "the rule has both COUNT and UNTIL"),
+            RuleError::InvalidDate(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl From<ParseError> for RuleError {
+    fn from(error: ParseError) -> RuleError {
+        RuleError::InvalidDate(error)
+    }
+}
+
+/// Parses the value of an RRULE property, e.g. `FREQ=WEEKLY;BYDAY=TU,TH`.
+impl FromStr for RecurrenceRule {
+    type Err = RuleError;
+
+    fn from_str(text: &str) -> Result<RecurrenceRule, RuleError> {
+        let mut frequency = None;
+        let mut rule = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_month: Vec::new(),
+            by_month_day: Vec::new(),
+            by_day: Vec::new(),
+            by_set_pos: Vec::new(),
+            week_start: Weekday::Monday,
+        };
+        for part in text.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=').unwrap_or((part, ""));
+            let invalid = || RuleError::InvalidValue(name.to_string(), value.to_string());
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "SECONDLY" => Frequency::Secondly,
+                        "MINUTELY" => Frequency::Minutely,
+                        "HOURLY" => Frequency::Hourly,
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value.parse().map_err(|_| invalid())?;
+                    if rule.interval == 0 {
+                        return Err(invalid());
+                    }
+                }
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| invalid())?),
+                "UNTIL" => rule.until = Some(value.parse::<Date>()?.epoch()),
+                "BYMONTH" => {
+                    rule.by_month = parse_list(value, |month| {
+                        month
+                            .parse::<u8>()
+                            .ok()
+                            .and_then(|m| Month::try_from(m).ok())
+                    })
+                    .ok_or_else(invalid)?
+                }
+                "BYMONTHDAY" => {
+                    rule.by_month_day = parse_list(value, |day| {
+                        day.parse::<i8>()
+                            .ok()
+                            .filter(|day| *day != 0 && (-31..=31).contains(day))
+                    })
+                    .ok_or_else(invalid)?
+                }
+                "BYDAY" => rule.by_day = parse_list(value, parse_by_day).ok_or_else(invalid)?,
+                "BYSETPOS" => {
+                    rule.by_set_pos = parse_list(value, |position| {
+                        position
+                            .parse::<i16>()
+                            .ok()
+                            .filter(|position| *position != 0 && (-366..=366).contains(position))
+                    })
+                    .ok_or_else(invalid)?
+                }
+                "WKST" => rule.week_start = parse_weekday(value).ok_or_else(invalid)?,
+                _ => return Err(RuleError::Unsupported(name.to_string())),
+            }
+        }
+        rule.frequency = frequency.ok_or(RuleError::MissingFrequency)?;
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err(RuleError::CountAndUntil);
+        }
+        Ok(rule)
+    }
+}
+
+fn parse_list<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Vec<T>> {
+    value.split(',').map(parse).collect()
+}
+
+fn parse_weekday(text: &str) -> Option<Weekday> {
+    let index = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"]
+        .iter()
+        .position(|name| *name == text)?;
+    Some(Weekday::ALL[index])
+}
+
+fn parse_by_day(text: &str) -> Option<ByDay> {
+    let split = text.len().checked_sub(2)?;
+    let (ordinal, weekday) = (text.get(..split)?, text.get(split..)?);
+    let ordinal = if ordinal.is_empty() {
+        None
+    } else {
+        let ordinal = ordinal.parse::<i8>().ok()?;
+        if ordinal == 0 || !(-53..=53).contains(&ordinal) {
+            return None;
+        }
+        Some(ordinal)
+    };
+    Some(ByDay {
+        ordinal,
+        weekday: parse_weekday(weekday)?,
+    })
+}
+
+/// The days since 1970 of a day of the month, where negative days count from the end.
+fn month_day(first: i64, days_in_month: u8, day: i8) -> Option<i64> {
+    let day = if day > 0 {
+        day as i64
+    } else {
+        days_in_month as i64 + day as i64 + 1
+    };
+    (1..=days_in_month as i64)
+        .contains(&day)
+        .then(|| first + day - 1)
+}
+
+fn weekday_of(days: i64) -> Weekday {
+    Weekday::from(EpochMS(days * 86_400_000))
+}
+
+impl RecurrenceRule {
+    /// Lazily expand the rule from a start date. Every occurrence has the same time of day
+    /// as the start, unless the frequency is more often than daily.
+    pub fn occurrences(&self, start: &Date) -> Occurrences {
+        Occurrences {
+            rule: self.clone(),
+            start: start.epoch(),
+            exceptions: Vec::new(),
+            period: 0,
+            last_found: start.epoch().0,
+            pending: Vec::new(),
+            emitted: 0,
+            done: false,
+        }
+    }
+
+    /// Whether the day passes the BYMONTH, BYMONTHDAY and BYDAY parts, ignoring the BYDAY
+    /// ordinals.
+    fn matches_day(&self, days: i64) -> bool {
+        let (year, month, day) = civil_from_days(days);
+        if !self.by_month.is_empty() && !self.by_month.contains(&month) {
+            return false;
+        }
+        if !self.by_month_day.is_empty() {
+            let first = days - day as i64 + 1;
+            let days_in_month = month.days(Gregorian.is_leap_year(year));
+            if !self
+                .by_month_day
+                .iter()
+                .any(|by_month_day| month_day(first, days_in_month, *by_month_day) == Some(days))
+            {
+                return false;
+            }
+        }
+        self.matches_weekday(days)
+    }
+
+    fn matches_weekday(&self, days: i64) -> bool {
+        self.by_day.is_empty()
+            || self
+                .by_day
+                .iter()
+                .any(|by_day| by_day.weekday == weekday_of(days))
+    }
+
+    /// The days in the span from `first` to `last` inclusive that match BYDAY, where the
+    /// ordinals count within the span.
+    fn expand_by_day(&self, first: i64, last: i64, days: &mut Vec<i64>) {
+        for by_day in &self.by_day {
+            let offset = (by_day.weekday.number_from_sunday() as i64
+                - weekday_of(first).number_from_sunday() as i64)
+                .rem_euclid(7);
+            let first_match = first + offset;
+            if first_match > last {
+                continue;
+            }
+            let count = (last - first_match) / 7 + 1;
+            match by_day.ordinal {
+                None => days.extend((first_match..=last).step_by(7)),
+                Some(ordinal) => {
+                    // Both -1 and `count` are the last match.
+                    let index = if ordinal > 0 {
+                        ordinal as i64 - 1
+                    } else {
+                        count + ordinal as i64
+                    };
+                    if (0..count).contains(&index) {
+                        days.push(first_match + index * 7);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The days of a month that the rule expands to, or the start's day of the month.
+    fn expand_month(&self, year: i64, month: Month, start_day: u8, days: &mut Vec<i64>) {
+        let first = days_from_civil(year, month, 1);
+        let days_in_month = month.days(Gregorian.is_leap_year(year));
+        if !self.by_month_day.is_empty() {
+            for day in &self.by_month_day {
+                days.extend(
+                    month_day(first, days_in_month, *day)
+                        .filter(|days| self.matches_weekday(*days)),
+                );
+            }
+        } else if !self.by_day.is_empty() {
+            self.expand_by_day(first, first + days_in_month as i64 - 1, days);
+        } else if start_day <= days_in_month {
+            days.push(first + start_day as i64 - 1);
+        }
+    }
+
+    /// The first instant of a period, and the candidates in it, before BYSETPOS. `None` once
+    /// the period is past `MAX_YEAR`, where a large interval could overflow.
+    fn expand_period(&self, start: EpochMS, period: i64) -> Option<(EpochMS, Vec<EpochMS>)> {
+        let start_date = Date::from(start);
+        let start_days = start.days_since();
+        let time_of_day = start.0 - start_days * 86_400_000;
+        let step = period.checked_mul(self.interval as i64)?;
+        let mut days = Vec::new();
+        let period_start = match self.frequency {
+            Frequency::Yearly => {
+                let year = start_date.year.checked_add(step)?;
+                if year > MAX_YEAR {
+                    return None;
+                }
+                if self.by_month.is_empty()
+                    && self.by_month_day.is_empty()
+                    && !self.by_day.is_empty()
+                {
+                    let first = days_from_civil(year, Month::January, 1);
+                    let last = days_from_civil(year, Month::December, 31);
+                    self.expand_by_day(first, last, &mut days);
+                } else {
+                    let months = if !self.by_month.is_empty() {
+                        self.by_month.clone()
+                    } else if !self.by_month_day.is_empty() {
+                        Month::ALL.to_vec()
+                    } else {
+                        vec![start_date.month]
+                    };
+                    for month in months {
+                        self.expand_month(year, month, start_date.day, &mut days);
+                    }
+                }
+                days_from_civil(year, Month::January, 1) * 86_400_000
+            }
+            Frequency::Monthly => {
+                let month = start_date.proleptic_month().checked_add(step)?;
+                let (year, month) = (
+                    month.div_euclid(12),
+                    Month::ALL[month.rem_euclid(12) as usize],
+                );
+                if year > MAX_YEAR {
+                    return None;
+                }
+                if self.by_month.is_empty() || self.by_month.contains(&month) {
+                    self.expand_month(year, month, start_date.day, &mut days);
+                }
+                days_from_civil(year, month, 1) * 86_400_000
+            }
+            Frequency::Weekly => {
+                let days_into_week = (start_date.weekday.number_from_sunday() as i64
+                    - self.week_start.number_from_sunday() as i64)
+                    .rem_euclid(7);
+                let week_start = (start_days - days_into_week).checked_add(step.checked_mul(7)?)?;
+                if week_start >= END_DAYS {
+                    return None;
+                }
+                if self.by_day.is_empty() {
+                    days.push(week_start + days_into_week);
+                } else {
+                    let mut weekdays: Vec<_> = self
+                        .by_day
+                        .iter()
+                        .map(|by_day| {
+                            (by_day.weekday.number_from_sunday() as i64
+                                - self.week_start.number_from_sunday() as i64)
+                                .rem_euclid(7)
+                        })
+                        .collect();
+                    weekdays.sort_unstable();
+                    days.extend(weekdays.into_iter().map(|offset| week_start + offset));
+                }
+                days.retain(|days| self.matches_day(*days));
+                week_start * 86_400_000
+            }
+            Frequency::Daily => {
+                let day = start_days.checked_add(step)?;
+                if day >= END_DAYS {
+                    return None;
+                }
+                if self.matches_day(day) {
+                    days.push(day);
+                }
+                day * 86_400_000
+            }
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let instant = start
+                    .0
+                    .checked_add(step.checked_mul(self.frequency_ms())?)?;
+                if instant.div_euclid(86_400_000) >= END_DAYS {
+                    return None;
+                }
+                let candidates = if self.matches_day(instant.div_euclid(86_400_000)) {
+                    vec![EpochMS(instant)]
+                } else {
+                    Vec::new()
+                };
+                return Some((EpochMS(instant), candidates));
+            }
+        };
+        days.sort_unstable();
+        days.dedup();
+        let candidates = days
+            .into_iter()
+            .map(|days| EpochMS(days * 86_400_000 + time_of_day))
+            .collect();
+        Some((EpochMS(period_start), candidates))
+    }
+
+    /// The length of a sub-daily period.
+    fn frequency_ms(&self) -> i64 {
+        match self.frequency {
+            Frequency::Hourly => 3_600_000,
+            Frequency::Minutely => 60_000,
+            _ => 1_000,
+        }
+    }
+
+    /// The period to expand after one that started at `period_start`. A sub-daily period
+    /// can only miss because its day does, so the rest of that day is skipped.
+    fn next_period(&self, start: EpochMS, period: i64, period_start: EpochMS, missed: bool) -> i64 {
+        if !missed || self.frequency >= Frequency::Daily {
+            return period + 1;
+        }
+        let step = self.interval as i64 * self.frequency_ms();
+        let next_day = (period_start.0.div_euclid(86_400_000) + 1) * 86_400_000;
+        let first_in_next_day = (next_day - start.0 + step - 1).div_euclid(step);
+        first_in_next_day.max(period + 1)
+    }
+
+    /// How long to go without an occurrence before giving up. A sub-daily rule only filters
+    /// by day, so its interval only matters once it spans more than a day.
+    fn give_up_ms(&self) -> i64 {
+        let periods = match self.frequency {
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let step = self.interval as i64 * self.frequency_ms();
+                (step + 86_400_000 - 1) / 86_400_000
+            }
+            _ => self.interval as i64,
+        };
+        GIVE_UP_MS.saturating_mul(periods)
+    }
+
+    fn select_positions(&self, candidates: Vec<EpochMS>) -> Vec<EpochMS> {
+        if self.by_set_pos.is_empty() {
+            return candidates;
+        }
+        let mut selected: Vec<_> = self
+            .by_set_pos
+            .iter()
+            .filter_map(|position| {
+                let index = if *position > 0 {
+                    *position as usize - 1
+                } else {
+                    candidates.len().checked_sub(-*position as usize)?
+                };
+                candidates.get(index).copied()
+            })
+            .collect();
+        selected.sort_unstable();
+        selected.dedup();
+        selected
+    }
+}
+
+/// A lazy iterator over the occurrences of a rule, in order.
+#[derive(Debug, Clone)]
+pub(super) struct Occurrences {
+    rule: RecurrenceRule,
+    start: EpochMS,
+    /// Sorted
+    exceptions: Vec<EpochMS>,
+    period: i64,
+    /// The start of the last period that had an occurrence.
+    last_found: i64,
+    /// The occurrences of the current period, in reverse order.
+    pending: Vec<EpochMS>,
+    emitted: u32,
+    done: bool,
+}
+
+/// If no period has matched in a full 400 year cycle of the Gregorian calendar, then none
+/// ever will.
+const GIVE_UP_MS: i64 = 146_097 * 86_400_000;
+
+/// The day after the last one a [Date] can be built for, where the expansion stops.
+const END_DAYS: i64 = days_from_civil(MAX_YEAR + 1, Month::January, 1);
+
+impl Occurrences {
+    /// Skip these dates, like the EXDATE property. They still count towards the COUNT of
+    /// the rule.
+    pub fn except(mut self, exceptions: impl IntoIterator<Item = EpochMS>) -> Occurrences {
+        self.exceptions.extend(exceptions);
+        self.exceptions.sort_unstable();
+        self
+    }
+
+    /// The occurrences as timestamps.
+    pub fn epochs(self) -> impl Iterator<Item = EpochMS> {
+        self.map(|date| date.epoch())
+    }
+
+    fn next_epoch(&mut self) -> Option<EpochMS> {
+        while !self.done {
+            if let Some(epoch) = self.pending.pop() {
+                if self.rule.until.is_some_and(|until| epoch > until) {
+                    break;
+                }
+                if self.rule.count.is_some_and(|count| self.emitted >= count) {
+                    break;
+                }
+                self.emitted += 1;
+                return Some(epoch);
+            }
+
+            let Some((period_start, candidates)) = self.rule.expand_period(self.start, self.period)
+            else {
+                break;
+            };
+            self.period =
+                self.rule
+                    .next_period(self.start, self.period, period_start, candidates.is_empty());
+            if self.rule.until.is_some_and(|until| period_start > until)
+                || period_start.0 - self.last_found > self.rule.give_up_ms()
+            {
+                break;
+            }
+            let start = self.start;
+            self.pending = self.rule.select_positions(candidates);
+            self.pending.retain(|epoch| *epoch >= start);
+            self.pending.reverse();
+            if !self.pending.is_empty() {
+                self.last_found = period_start.0;
+            }
+        }
+        self.done = true;
+        None
+    }
+}
+
+impl Iterator for Occurrences {
+    type Item = Date;
+
+    fn next(&mut self) -> Option<Date> {
+        loop {
+            let epoch = self.next_epoch()?;
+            if self.exceptions.binary_search(&epoch).is_err() {
+                return Some(Date::from(epoch));
+            }
+        }
+    }
+}
+
+/// The DTSTART, RRULE and EXDATE properties of an iCalendar event, e.g.
+///
+/// ```text
+/// DTSTART:19970902T090000Z
+/// RRULE:FREQ=DAILY;COUNT=10
+/// EXDATE:19970903T090000Z,19970905T090000Z
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub start: Date,
+    pub rule: RecurrenceRule,
+    pub exceptions: Vec<EpochMS>,
+}
+
+impl Recurrence {
+    pub fn occurrences(&self) -> Occurrences {
+        self.rule
+            .occurrences(&self.start)
+            .except(self.exceptions.iter().copied())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = RuleError;
+
+    fn from_str(text: &str) -> Result<Recurrence, RuleError> {
+        let mut start = None;
+        let mut rule = None;
+        let mut exceptions = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| RuleError::Unsupported(line.to_string()))?;
+            let (name, parameters) = name.split_once(';').unwrap_or((name, ""));
+            if parameters
+                .split(';')
+                .any(|parameter| parameter.starts_with("TZID="))
+            {
+                return Err(RuleError::Unsupported(format!("{};{}", name, parameters)));
+            }
+            match name {
+                "DTSTART" => start = Some(value.parse::<Date>()?),
+                "RRULE" => rule = Some(value.parse()?),
+                "EXDATE" => {
+                    for date in value.split(',') {
+                        exceptions.push(date.parse::<Date>()?.epoch());
+                    }
+                }
+                _ => return Err(RuleError::Unsupported(name.to_string())),
+            }
+        }
+        Ok(Recurrence {
+            start: start.ok_or_else(|| RuleError::Unsupported("a rule without DTSTART".into()))?,
+            rule: rule.ok_or(RuleError::MissingFrequency)?,
+            exceptions,
+        })
+    }
+}
+
+#[cfg(test)]
+fn expand(start: &str, rule: &str, limit: usize) -> Vec<String> {
+    let start: Date = start.parse().unwrap();
+    let rule: RecurrenceRule = rule.parse().unwrap();
+    rule.occurrences(&start)
+        .take(limit)
+        .map(|date| {
+            let precision = if date.hour == 9 && date.minute == 0 {
+                super::iso8601::Precision::Days
+            } else {
+                super::iso8601::Precision::Minutes
+            };
+            date.to_iso8601(precision)
+        })
+        .collect()
+}
+
+/// The examples from RFC 5545 section 3.8.5.3, in UTC rather than New York time. Times of
+/// 09:00 are left off.
+#[test]
+fn test_rfc_daily_weekly() {
+    let start = "19970902T090000";
+    assert_eq!(
+        expand(start, "FREQ=DAILY;COUNT=10", 100),
+        [
+            "1997-09-02",
+            "1997-09-03",
+            "1997-09-04",
+            "1997-09-05",
+            "1997-09-06",
+            "1997-09-07",
+            "1997-09-08",
+            "1997-09-09",
+            "1997-09-10",
+            "1997-09-11"
+        ]
+    );
+    let until = expand(start, "FREQ=DAILY;UNTIL=19971224T000000Z", 1000);
+    assert_eq!(until.len(), 113);
+    assert_eq!(until.last().unwrap(), "1997-12-23");
+    assert_eq!(
+        expand(start, "FREQ=DAILY;INTERVAL=2", 4),
+        ["1997-09-02", "1997-09-04", "1997-09-06", "1997-09-08"]
+    );
+    assert_eq!(
+        expand(start, "FREQ=DAILY;INTERVAL=10;COUNT=5", 100),
+        [
+            "1997-09-02",
+            "1997-09-12",
+            "1997-09-22",
+            "1997-10-02",
+            "1997-10-12"
+        ]
+    );
+    assert_eq!(
+        expand(start, "FREQ=WEEKLY;COUNT=10", 100),
+        [
+            "1997-09-02",
+            "1997-09-09",
+            "1997-09-16",
+            "1997-09-23",
+            "1997-09-30",
+            "1997-10-07",
+            "1997-10-14",
+            "1997-10-21",
+            "1997-10-28",
+            "1997-11-04"
+        ]
+    );
+    assert_eq!(
+        expand(
+            start,
+            "FREQ=WEEKLY;UNTIL=19971007T000000Z;WKST=SU;BYDAY=TU,TH",
+            100
+        ),
+        [
+            "1997-09-02",
+            "1997-09-04",
+            "1997-09-09",
+            "1997-09-11",
+            "1997-09-16",
+            "1997-09-18",
+            "1997-09-23",
+            "1997-09-25",
+            "1997-09-30",
+            "1997-10-02"
+        ]
+    );
+    assert_eq!(
+        expand(
+            "19970901T090000",
+            "FREQ=WEEKLY;INTERVAL=2;UNTIL=19971224T000000Z;WKST=SU;BYDAY=MO,WE,FR",
+            100
+        ),
+        [
+            "1997-09-01",
+            "1997-09-03",
+            "1997-09-05",
+            "1997-09-15",
+            "1997-09-17",
+            "1997-09-19",
+            "1997-09-29",
+            "1997-10-01",
+            "1997-10-03",
+            "1997-10-13",
+            "1997-10-15",
+            "1997-10-17",
+            "1997-10-27",
+            "1997-10-29",
+            "1997-10-31",
+            "1997-11-10",
+            "1997-11-12",
+            "1997-11-14",
+            "1997-11-24",
+            "1997-11-26",
+            "1997-11-28",
+            "1997-12-08",
+            "1997-12-10",
+            "1997-12-12",
+            "1997-12-22"
+        ]
+    );
+
+    // The week start changes which days are grouped into each week.
+    let start = "19970805T090000";
+    assert_eq!(
+        expand(
+            start,
+            "FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=MO",
+            100
+        ),
+        ["1997-08-05", "1997-08-10", "1997-08-19", "1997-08-24"]
+    );
+    assert_eq!(
+        expand(
+            start,
+            "FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=SU",
+            100
+        ),
+        ["1997-08-05", "1997-08-17", "1997-08-19", "1997-08-31"]
+    );
+}
+
+#[test]
+fn test_rfc_monthly() {
+    assert_eq!(
+        expand("19970905T090000", "FREQ=MONTHLY;COUNT=10;BYDAY=1FR", 100),
+        [
+            "1997-09-05",
+            "1997-10-03",
+            "1997-11-07",
+            "1997-12-05",
+            "1998-01-02",
+            "1998-02-06",
+            "1998-03-06",
+            "1998-04-03",
+            "1998-05-01",
+            "1998-06-05"
+        ]
+    );
+    assert_eq!(
+        expand(
+            "19970907T090000",
+            "FREQ=MONTHLY;INTERVAL=2;COUNT=10;BYDAY=1SU,-1SU",
+            100
+        ),
+        [
+            "1997-09-07",
+            "1997-09-28",
+            "1997-11-02",
+            "1997-11-30",
+            "1998-01-04",
+            "1998-01-25",
+            "1998-03-01",
+            "1998-03-29",
+            "1998-05-03",
+            "1998-05-31"
+        ]
+    );
+    assert_eq!(
+        expand("19970922T090000", "FREQ=MONTHLY;COUNT=6;BYDAY=-2MO", 100),
+        [
+            "1997-09-22",
+            "1997-10-20",
+            "1997-11-17",
+            "1997-12-22",
+            "1998-01-19",
+            "1998-02-16"
+        ]
+    );
+    assert_eq!(
+        expand("19970928T090000", "FREQ=MONTHLY;BYMONTHDAY=-3", 6),
+        [
+            "1997-09-28",
+            "1997-10-29",
+            "1997-11-28",
+            "1997-12-29",
+            "1998-01-29",
+            "1998-02-26"
+        ]
+    );
+    assert_eq!(
+        expand(
+            "19970902T090000",
+            "FREQ=MONTHLY;COUNT=10;BYMONTHDAY=2,15",
+            100
+        ),
+        [
+            "1997-09-02",
+            "1997-09-15",
+            "1997-10-02",
+            "1997-10-15",
+            "1997-11-02",
+            "1997-11-15",
+            "1997-12-02",
+            "1997-12-15",
+            "1998-01-02",
+            "1998-01-15"
+        ]
+    );
+    // Months without the day are skipped, rather than clamped.
+    assert_eq!(
+        expand(
+            "20070115T090000",
+            "FREQ=MONTHLY;BYMONTHDAY=15,30;COUNT=5",
+            100
+        ),
+        [
+            "2007-01-15",
+            "2007-01-30",
+            "2007-02-15",
+            "2007-03-15",
+            "2007-03-30"
+        ]
+    );
+    // The last work day of the month.
+    assert_eq!(
+        expand(
+            "19970929T090000",
+            "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+            7
+        ),
+        [
+            "1997-09-30",
+            "1997-10-31",
+            "1997-11-28",
+            "1997-12-31",
+            "1998-01-30",
+            "1998-02-27",
+            "1998-03-31"
+        ]
+    );
+    assert_eq!(
+        expand(
+            "19970904T090000",
+            "FREQ=MONTHLY;COUNT=3;BYDAY=TU,WE,TH;BYSETPOS=3",
+            100
+        ),
+        ["1997-09-04", "1997-10-07", "1997-11-06"]
+    );
+}
+
+#[test]
+fn test_rfc_yearly_and_hourly() {
+    assert_eq!(
+        expand("19970610T090000", "FREQ=YEARLY;COUNT=10;BYMONTH=6,7", 100),
+        [
+            "1997-06-10",
+            "1997-07-10",
+            "1998-06-10",
+            "1998-07-10",
+            "1999-06-10",
+            "1999-07-10",
+            "2000-06-10",
+            "2000-07-10",
+            "2001-06-10",
+            "2001-07-10"
+        ]
+    );
+    assert_eq!(
+        expand("19970519T090000", "FREQ=YEARLY;BYDAY=20MO", 3),
+        ["1997-05-19", "1998-05-18", "1999-05-17"]
+    );
+    assert_eq!(
+        expand("19970313T090000", "FREQ=YEARLY;BYMONTH=3;BYDAY=TH", 11),
+        [
+            "1997-03-13",
+            "1997-03-20",
+            "1997-03-27",
+            "1998-03-05",
+            "1998-03-12",
+            "1998-03-19",
+            "1998-03-26",
+            "1999-03-04",
+            "1999-03-11",
+            "1999-03-18",
+            "1999-03-25"
+        ]
+    );
+    // February 29 only happens in leap years.
+    assert_eq!(
+        expand("20000229T090000", "FREQ=YEARLY;COUNT=3", 100),
+        ["2000-02-29", "2004-02-29", "2008-02-29"]
+    );
+    assert_eq!(
+        expand(
+            "19970902T090000",
+            "FREQ=HOURLY;INTERVAL=3;UNTIL=19970902T170000Z",
+            100
+        ),
+        ["1997-09-02", "1997-09-02T12:00Z", "1997-09-02T15:00Z"]
+    );
+    assert_eq!(
+        expand("19970902T090000", "FREQ=MINUTELY;INTERVAL=15;COUNT=6", 100),
+        [
+            "1997-09-02",
+            "1997-09-02T09:15Z",
+            "1997-09-02T09:30Z",
+            "1997-09-02T09:45Z",
+            "1997-09-02T10:00Z",
+            "1997-09-02T10:15Z"
+        ]
+    );
+}
+
+#[test]
+fn test_exdate() {
+    // Friday the 13th, where the start doesn't match the rule.
+    let recurrence: Recurrence = "DTSTART:19970902T090000Z
+        EXDATE:19970902T090000Z
+        RRULE:FREQ=MONTHLY;BYDAY=FR;BYMONTHDAY=13"
+        .parse()
+        .unwrap();
+    let dates: Vec<_> = recurrence
+        .occurrences()
+        .take(5)
+        .map(|date| date.to_iso8601(super::iso8601::Precision::Days))
+        .collect();
+    assert_eq!(
+        dates,
+        [
+            "1998-02-13",
+            "1998-03-13",
+            "1998-11-13",
+            "1999-08-13",
+            "2000-10-13"
+        ]
+    );
+
+    // Exceptions still count towards the COUNT.
+    let recurrence: Recurrence = "DTSTART:19970902T090000Z
+        RRULE:FREQ=DAILY;COUNT=5
+        EXDATE:19970903T090000Z,19970905T090000Z"
+        .parse()
+        .unwrap();
+    let epochs: Vec<_> = recurrence.occurrences().epochs().collect();
+    let expected: Vec<_> = ["19970902T090000Z", "19970904T090000Z", "19970906T090000Z"]
+        .iter()
+        .map(|date| date.parse::<Date>().unwrap().epoch())
+        .collect();
+    assert_eq!(epochs, expected);
+}
+
+#[test]
+fn test_rule_errors() {
+    let start: Date = "20210201T000000Z".parse().unwrap();
+    assert_eq!(
+        "COUNT=3".parse::<RecurrenceRule>(),
+        Err(RuleError::MissingFrequency)
+    );
+    assert_eq!(
+        "FREQ=DAILY;BYWEEKNO=20".parse::<RecurrenceRule>(),
+        Err(RuleError::Unsupported("BYWEEKNO".into()))
+    );
+    assert_eq!(
+        "FREQ=DAILY;BYDAY=XX".parse::<RecurrenceRule>(),
+        Err(RuleError::InvalidValue("BYDAY".into(), "XX".into()))
+    );
+    assert_eq!(
+        "FREQ=DAILY;INTERVAL=0".parse::<RecurrenceRule>(),
+        Err(RuleError::InvalidValue("INTERVAL".into(), "0".into()))
+    );
+    assert_eq!(
+        "FREQ=DAILY;COUNT=2;UNTIL=20210301".parse::<RecurrenceRule>(),
+        Err(RuleError::CountAndUntil)
+    );
+    assert!(
+        "DTSTART;TZID=America/New_York:19970902T090000\nRRULE:FREQ=DAILY"
+            .parse::<Recurrence>()
+            .is_err()
+    );
+
+    // A rule that never matches ends rather than looping forever, including one that would
+    // take a step every second.
+    let rule: RecurrenceRule = "FREQ=DAILY;BYMONTH=2;BYMONTHDAY=30".parse().unwrap();
+    assert_eq!(rule.occurrences(&start).next(), None);
+    let rule: RecurrenceRule = "FREQ=MONTHLY;BYMONTHDAY=31;BYMONTH=4".parse().unwrap();
+    assert_eq!(rule.occurrences(&start).next(), None);
+    let rule: RecurrenceRule = "FREQ=SECONDLY;BYMONTH=2;BYMONTHDAY=30".parse().unwrap();
+    assert_eq!(rule.occurrences(&start).next(), None);
+}
+
+#[test]
+fn test_far_from_epoch() {
+    // How long a rule goes without matching is measured from the start, not from 1970.
+    assert_eq!(
+        expand("25000101T090000", "FREQ=YEARLY;COUNT=3", 100),
+        ["2500-01-01", "2501-01-01", "2502-01-01"]
+    );
+    assert_eq!(
+        expand("24500301T090000", "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=29", 2),
+        ["2452-02-29", "2456-02-29"]
+    );
+
+    // The days that don't match are skipped, but the steps still line up with the start.
+    assert_eq!(
+        expand("19970902T090000", "FREQ=MINUTELY;INTERVAL=7;BYDAY=SA", 3),
+        [
+            "1997-09-06T00:02Z",
+            "1997-09-06T00:09Z",
+            "1997-09-06T00:16Z"
+        ]
+    );
+    // Every 29 hours lands on the 1st at a different time each month.
+    assert_eq!(
+        expand("19970902T090000", "FREQ=HOURLY;INTERVAL=29;BYMONTHDAY=1", 2),
+        ["1997-10-01", "1997-11-01T19:00Z"]
+    );
+
+    // An interval that jumps past the last year ends the rule, rather than overflowing.
+    for frequency in ["YEARLY", "MONTHLY", "WEEKLY", "DAILY"] {
+        let rule = format!("FREQ={};INTERVAL=4294967295", frequency);
+        assert_eq!(
+            expand("19970902T090000", &rule, 3),
+            ["1997-09-02"],
+            "{}",
+            rule
+        );
+    }
+}
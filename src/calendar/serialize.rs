@@ -0,0 +1,346 @@
+//! Serde support for the calendar types, behind the `serde` feature. By default a [Date] is
+//! an RFC 3339 string, an [EpochMS] is an integer of milliseconds, and a [Month] or
+//! [Weekday] is its English name. Dates and timestamps can pick a different representation
+//! with `#[serde(with = "...")]`, using the [rfc3339], [millis] or [fields] modules.
+
+use super::{Date, EpochMS, Month, Weekday};
+use ::serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        rfc3339::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Date, D::Error> {
+        rfc3339::deserialize(deserializer)
+    }
+}
+
+impl Serialize for EpochMS {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        millis::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EpochMS {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<EpochMS, D::Error> {
+        millis::deserialize(deserializer)
+    }
+}
+
+impl Serialize for Month {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(MONTH_NAMES[self.number() as usize - 1])
+    }
+}
+
+impl<'de> Deserialize<'de> for Month {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Month, D::Error> {
+        deserializer.deserialize_str(NameVisitor {
+            names: &MONTH_NAMES,
+            values: &Month::ALL,
+        })
+    }
+}
+
+impl Serialize for Weekday {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(WEEKDAY_NAMES[self.number_from_sunday() as usize])
+    }
+}
+
+impl<'de> Deserialize<'de> for Weekday {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Weekday, D::Error> {
+        deserializer.deserialize_str(NameVisitor {
+            names: &WEEKDAY_NAMES,
+            values: &Weekday::ALL,
+        })
+    }
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Finds a name, and returns the value at the same index.
+struct NameVisitor<T: 'static> {
+    names: &'static [&'static str],
+    values: &'static [T],
+}
+
+impl<'de, T: Copy> de::Visitor<'de> for NameVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "one of {}", self.names.join(", "))
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<T, E> {
+        self.names
+            .iter()
+            .position(|candidate| *candidate == name)
+            .map(|index| self.values[index])
+            .ok_or_else(|| E::unknown_variant(name, self.names))
+    }
+}
+
+/// The types that can be serialized with any of the representations below.
+pub trait Timestamp: Sized {
+    fn to_date(&self) -> Date;
+    fn to_epoch(&self) -> EpochMS;
+    fn from_date(date: Date) -> Self;
+    fn from_epoch(epoch: EpochMS) -> Self;
+}
+
+impl Timestamp for Date {
+    fn to_date(&self) -> Date {
+        self.clone()
+    }
+
+    fn to_epoch(&self) -> EpochMS {
+        self.epoch()
+    }
+
+    fn from_date(date: Date) -> Date {
+        date
+    }
+
+    fn from_epoch(epoch: EpochMS) -> Date {
+        Date::from(epoch)
+    }
+}
+
+impl Timestamp for EpochMS {
+    fn to_date(&self) -> Date {
+        Date::from(*self)
+    }
+
+    fn to_epoch(&self) -> EpochMS {
+        *self
+    }
+
+    fn from_date(date: Date) -> EpochMS {
+        date.epoch()
+    }
+
+    fn from_epoch(epoch: EpochMS) -> EpochMS {
+        epoch
+    }
+}
+
+/// An RFC 3339 string in UTC with milliseconds, e.g. `2021-02-01T17:19:05.123Z`. Years
+/// outside of 0 to 9999 use the expanded ISO 8601 form, e.g. `-000044-03-15T00:00:00.000Z`.
+/// Any offset is accepted when deserializing.
+pub mod rfc3339 {
+    use super::super::iso8601::Precision;
+    use super::{de, Deserialize, Deserializer, Serializer, Timestamp};
+
+    pub fn serialize<T: Timestamp, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_date().to_iso8601(Precision::Milliseconds))
+    }
+
+    pub fn deserialize<'de, T: Timestamp, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let text = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        let date = text.parse().map_err(de::Error::custom)?;
+        Ok(T::from_date(date))
+    }
+}
+
+/// An integer of the milliseconds since 1970.
+pub mod millis {
+    use super::{Deserialize, Deserializer, EpochMS, Serializer, Timestamp};
+
+    pub fn serialize<T: Timestamp, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(value.to_epoch().0)
+    }
+
+    pub fn deserialize<'de, T: Timestamp, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        Ok(T::from_epoch(EpochMS(i64::deserialize(deserializer)?)))
+    }
+}
+
+/// A struct of the calendar fields in UTC, e.g. `{"year": 2021, "month": 2, "day": 1, ...}`.
+/// The month is a number, and the fields are validated when deserializing.
+pub mod fields {
+    use super::{de, Date, Deserialize, Deserializer, Serialize, Serializer, Timestamp};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Date")]
+    struct Fields {
+        year: i64,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        millisecond: u16,
+    }
+
+    pub fn serialize<T: Timestamp, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let date = value.to_date();
+        Fields {
+            year: date.year,
+            month: date.month.number(),
+            day: date.day,
+            hour: date.hour,
+            minute: date.minute,
+            second: date.second,
+            millisecond: date.millisecond,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Timestamp, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let fields = Fields::deserialize(deserializer)?;
+        let date = fields
+            .month
+            .try_into()
+            .and_then(|month| {
+                Date::new(
+                    fields.year,
+                    month,
+                    fields.day,
+                    fields.hour,
+                    fields.minute,
+                    fields.second,
+                    fields.millisecond,
+                )
+            })
+            .map_err(de::Error::custom)?;
+        Ok(T::from_date(date))
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Event {
+    start: Date,
+    #[serde(with = "millis")]
+    end: Date,
+    #[serde(with = "fields")]
+    reminder: Date,
+    created: EpochMS,
+    #[serde(with = "rfc3339")]
+    updated: EpochMS,
+    month: Month,
+    weekday: Weekday,
+}
+
+#[test]
+fn test_serde_representations() {
+    let date = Date::new(2021, Month::February, 1, 17, 19, 5, 123).unwrap();
+    let event = Event {
+        start: date.clone(),
+        end: date.clone(),
+        reminder: date.clone(),
+        created: date.epoch(),
+        updated: date.epoch(),
+        month: Month::February,
+        weekday: Weekday::Monday,
+    };
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "start": "2021-02-01T17:19:05.123Z",
+            "end": 1_612_199_945_123_i64,
+            "reminder": {
+                "year": 2021,
+                "month": 2,
+                "day": 1,
+                "hour": 17,
+                "minute": 19,
+                "second": 5,
+                "millisecond": 123,
+            },
+            "created": 1_612_199_945_123_i64,
+            "updated": "2021-02-01T17:19:05.123Z",
+            "month": "February",
+            "weekday": "Monday",
+        })
+    );
+    assert_eq!(serde_json::from_value::<Event>(json).unwrap(), event);
+
+    let date = Date::new(-44, Month::March, 15, 0, 0, 0, 0).unwrap();
+    let json = serde_json::to_string(&date).unwrap();
+    assert_eq!(json, r#""-000044-03-15T00:00:00.000Z""#);
+    assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), date);
+
+    // A leap second survives the trip through the string and the fields.
+    let leap = Date::new(2016, Month::December, 31, 23, 59, 60, 500).unwrap();
+    let json = serde_json::to_string(&leap).unwrap();
+    assert_eq!(json, r#""2016-12-31T23:59:60.500Z""#);
+    assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), leap);
+    let json = fields::serialize(&leap, serde_json::value::Serializer).unwrap();
+    assert_eq!(json["second"], 60);
+    assert_eq!(fields::deserialize::<Date, _>(json).unwrap(), leap);
+}
+
+#[test]
+fn test_serde_errors() {
+    assert_eq!(
+        serde_json::from_str::<Date>(r#""2021-02-01T17:19:05+01:00""#).unwrap(),
+        Date::new(2021, Month::February, 1, 16, 19, 5, 0).unwrap()
+    );
+    assert!(serde_json::from_str::<Date>(r#""2021-02-30T00:00:00Z""#).is_err());
+    assert!(serde_json::from_str::<Date>("1612199945123").is_err());
+
+    assert_eq!(
+        serde_json::from_str::<Month>(r#""March""#).unwrap(),
+        Month::March
+    );
+    assert!(serde_json::from_str::<Month>("3").is_err());
+    assert!(serde_json::from_str::<Month>(r#""Mars""#).is_err());
+    assert!(serde_json::from_str::<Weekday>(r#""Tueday""#).is_err());
+
+    let error = fields::deserialize::<Date, _>(serde_json::json!({
+        "year": 2021,
+        "month": 2,
+        "day": 29,
+        "hour": 0,
+        "minute": 0,
+        "second": 0,
+        "millisecond": 0,
+    }))
+    .unwrap_err();
+    assert_eq!(error.to_string(), "February 29, 2021 does not exist");
+}
@@ -0,0 +1,785 @@
+//! Calendar systems other than the proleptic Gregorian calendar that [Date] uses. Every
+//! calendar converts its dates to and from a [FixedDay], a count of days shared by all of
+//! them, so a date in one calendar converts to any other through the fixed day, or through
+//! [EpochMS]. The algorithms are from "Calendrical Calculations" by Reingold and Dershowitz.
+//!
+//! Months and days are plain numbers starting from 1, as they don't line up between
+//! calendars. Years use astronomical numbering where a calendar has years before 1, so the
+//! year before 1 is 0.
+
+use super::week::WeekRule;
+use super::{
+    civil_from_days, days_from_civil, Date, DateError, EpochMS, Month, MAX_YEAR, MIN_YEAR,
+};
+
+/// The fixed day number, or Rata Die, counts days from 1 at January 1, 1 in the proleptic
+/// Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixedDay(pub i64);
+
+/// The fixed day of January 1, 1970.
+const UNIX_EPOCH_FIXED: i64 = 719_163;
+
+impl FixedDay {
+    /// Midnight UTC at the start of the day.
+    pub const fn epoch(&self) -> EpochMS {
+        EpochMS((self.0 - UNIX_EPOCH_FIXED) * 86_400_000)
+    }
+}
+
+impl From<EpochMS> for FixedDay {
+    fn from(epoch: EpochMS) -> FixedDay {
+        FixedDay(epoch.days_since() + UNIX_EPOCH_FIXED)
+    }
+}
+
+pub trait Calendar: Copy + Eq + std::fmt::Debug {
+    /// The year is a number in most calendars, but needs an era in others.
+    type Year: Copy + Eq + std::fmt::Debug;
+
+    const NAME: &'static str;
+
+    fn is_leap_year(&self, year: Self::Year) -> bool;
+
+    fn months_in_year(&self, year: Self::Year) -> u8;
+
+    /// The month must exist in the year.
+    fn days_in_month(&self, year: Self::Year, month: u8) -> u8;
+
+    fn days_in_year(&self, year: Self::Year) -> u16 {
+        (1..=self.months_in_year(year))
+            .map(|month| self.days_in_month(year, month) as u16)
+            .sum()
+    }
+
+    /// The month and the day of the month of a day of the year, all starting from 1. The
+    /// day of the year must exist in the year.
+    fn day_month(&self, year: Self::Year, day_of_year: u16) -> (u8, u8) {
+        let mut day = day_of_year;
+        for month in 1..self.months_in_year(year) {
+            let days_in_month = self.days_in_month(year, month) as u16;
+            if day <= days_in_month {
+                return (month, day as u8);
+            }
+            day -= days_in_month;
+        }
+        (self.months_in_year(year), day as u8)
+    }
+
+    /// The year as it's written, which for eras is the year of the era.
+    fn year_number(&self, year: Self::Year) -> i64;
+
+    /// The fixed day of a date. The fields are not validated.
+    fn fixed_from_date(&self, year: Self::Year, month: u8, day: u8) -> FixedDay;
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (Self::Year, u8, u8);
+}
+
+/// A day in one of the calendar systems, without a time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarDate<C: Calendar> {
+    pub calendar: C,
+    pub year: C::Year,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl<C: Calendar> CalendarDate<C> {
+    pub fn new(calendar: C, year: C::Year, month: u8, day: u8) -> Result<Self, DateError> {
+        let year_number = calendar.year_number(year);
+        if !(MIN_YEAR..=MAX_YEAR).contains(&year_number) {
+            return Err(DateError::YearOutOfRange(year_number));
+        }
+        let error = DateError::InvalidCalendarDate {
+            calendar: C::NAME,
+            year: year_number,
+            month,
+            day,
+        };
+        if month == 0 || month > calendar.months_in_year(year) {
+            return Err(error);
+        }
+        if day == 0 || day > calendar.days_in_month(year, month) {
+            return Err(error);
+        }
+        // Catches the dates that don't exist for other reasons, such as being outside of
+        // their era.
+        let fixed = calendar.fixed_from_date(year, month, day);
+        if calendar.date_from_fixed(fixed) != (year, month, day) {
+            return Err(error);
+        }
+        Ok(CalendarDate {
+            calendar,
+            year,
+            month,
+            day,
+        })
+    }
+
+    pub fn from_fixed(calendar: C, fixed: FixedDay) -> Self {
+        let (year, month, day) = calendar.date_from_fixed(fixed);
+        CalendarDate {
+            calendar,
+            year,
+            month,
+            day,
+        }
+    }
+
+    /// The day that the timestamp falls in, in UTC.
+    pub fn from_epoch(calendar: C, epoch: EpochMS) -> Self {
+        CalendarDate::from_fixed(calendar, FixedDay::from(epoch))
+    }
+
+    pub fn fixed(&self) -> FixedDay {
+        self.calendar
+            .fixed_from_date(self.year, self.month, self.day)
+    }
+
+    /// Midnight UTC at the start of the day.
+    pub fn epoch(&self) -> EpochMS {
+        self.fixed().epoch()
+    }
+
+    /// The same day in another calendar.
+    pub fn to_calendar<D: Calendar>(self, calendar: D) -> CalendarDate<D> {
+        CalendarDate::from_epoch(calendar, self.epoch())
+    }
+}
+
+impl Date {
+    /// The day of this date in another calendar, dropping the time of day.
+    pub fn to_calendar<C: Calendar>(&self, calendar: C) -> CalendarDate<C> {
+        CalendarDate::from_epoch(calendar, self.epoch())
+    }
+}
+
+impl<C: Calendar> From<CalendarDate<C>> for Date {
+    fn from(date: CalendarDate<C>) -> Date {
+        Date::from(date.epoch())
+    }
+}
+
+/// The proleptic Gregorian calendar, with the same fields as [Date].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gregorian;
+
+impl Calendar for Gregorian {
+    type Year = i64;
+
+    const NAME: &'static str = "Gregorian";
+
+    /// Every year that is exactly divisible by four is a leap year, except for years that
+    /// are exactly divisible by 100, but these centurial years are leap years if they are
+    /// exactly divisible by 400. For example, the years 1700, 1800, and 1900 are not leap
+    /// years, but the years 1600 and 2000 are.
+    fn is_leap_year(&self, year: i64) -> bool {
+        if year % 4 != 0 {
+            return false;
+        }
+        year % 100 != 0 || year % 400 == 0
+    }
+
+    fn months_in_year(&self, _year: i64) -> u8 {
+        12
+    }
+
+    fn days_in_month(&self, year: i64, month: u8) -> u8 {
+        Month::ALL[month as usize - 1].days(self.is_leap_year(year))
+    }
+
+    fn days_in_year(&self, year: i64) -> u16 {
+        if self.is_leap_year(year) {
+            366
+        } else {
+            365
+        }
+    }
+
+    fn year_number(&self, year: i64) -> i64 {
+        year
+    }
+
+    fn fixed_from_date(&self, year: i64, month: u8, day: u8) -> FixedDay {
+        let month = Month::ALL[month as usize - 1];
+        FixedDay(days_from_civil(year, month, day) + UNIX_EPOCH_FIXED)
+    }
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (i64, u8, u8) {
+        let (year, month, day) = civil_from_days(fixed.0 - UNIX_EPOCH_FIXED);
+        (year, month.number(), day)
+    }
+}
+
+/// The proleptic Julian calendar, where every fourth year is a leap year. It has the same
+/// months as the Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Julian;
+
+/// The fixed day of January 1, 1 in the Julian calendar, which is December 30, 0 in the
+/// Gregorian calendar.
+const JULIAN_EPOCH: i64 = -1;
+
+impl Calendar for Julian {
+    type Year = i64;
+
+    const NAME: &'static str = "Julian";
+
+    fn is_leap_year(&self, year: i64) -> bool {
+        year.rem_euclid(4) == 0
+    }
+
+    fn months_in_year(&self, _year: i64) -> u8 {
+        12
+    }
+
+    fn days_in_month(&self, year: i64, month: u8) -> u8 {
+        Month::ALL[month as usize - 1].days(self.is_leap_year(year))
+    }
+
+    fn year_number(&self, year: i64) -> i64 {
+        year
+    }
+
+    fn fixed_from_date(&self, year: i64, month: u8, day: u8) -> FixedDay {
+        let month = month as i64;
+        // Counts February as 30 days, and then corrects for it after February.
+        let february_correction = if month <= 2 {
+            0
+        } else if self.is_leap_year(year) {
+            -1
+        } else {
+            -2
+        };
+        FixedDay(
+            JULIAN_EPOCH - 1
+                + 365 * (year - 1)
+                + (year - 1).div_euclid(4)
+                + (367 * month - 362) / 12
+                + february_correction
+                + day as i64,
+        )
+    }
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (i64, u8, u8) {
+        let year = (4 * (fixed.0 - JULIAN_EPOCH) + 1464).div_euclid(1461);
+        let prior_days = fixed.0 - self.fixed_from_date(year, 1, 1).0;
+        let correction = if fixed < self.fixed_from_date(year, 3, 1) {
+            0
+        } else if self.is_leap_year(year) {
+            1
+        } else {
+            2
+        };
+        let month = ((12 * (prior_days + correction) + 373) / 367) as u8;
+        let day = fixed.0 - self.fixed_from_date(year, month, 1).0 + 1;
+        (year, month, day as u8)
+    }
+}
+
+/// The ISO 8601 week calendar, where the "month" is the week of the year, and the "day" is
+/// the weekday, from 1 for Monday to 7 for Sunday. The years are week-based years, see
+/// [WeekRule::ISO].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Iso;
+
+impl Calendar for Iso {
+    type Year = i64;
+
+    const NAME: &'static str = "ISO week";
+
+    /// Leap years have 53 weeks.
+    fn is_leap_year(&self, year: i64) -> bool {
+        self.months_in_year(year) == 53
+    }
+
+    fn months_in_year(&self, year: i64) -> u8 {
+        WeekRule::ISO.weeks_in_year(year)
+    }
+
+    fn days_in_month(&self, _year: i64, _week: u8) -> u8 {
+        7
+    }
+
+    fn year_number(&self, year: i64) -> i64 {
+        year
+    }
+
+    fn fixed_from_date(&self, year: i64, week: u8, weekday: u8) -> FixedDay {
+        let days = WeekRule::ISO.first_week_start(year) + (week as i64 - 1) * 7;
+        FixedDay(days + weekday as i64 - 1 + UNIX_EPOCH_FIXED)
+    }
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (i64, u8, u8) {
+        let days = fixed.0 - UNIX_EPOCH_FIXED;
+        let week = WeekRule::ISO.week_of_day(days);
+        let weekday = days - WeekRule::ISO.first_week_start(week.year) - (week.week as i64 - 1) * 7;
+        (week.year, week.week, weekday as u8 + 1)
+    }
+}
+
+/// The arithmetic Islamic calendar, which approximates the lunar months with a 30 year cycle
+/// of 11 leap years. Months alternate between 30 and 29 days, and the last month gets an
+/// extra day in leap years. The observational calendar can differ by a day or two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IslamicTabular;
+
+/// July 16, 622 in the Julian calendar, a Friday.
+const ISLAMIC_EPOCH: i64 = 227_015;
+
+impl Calendar for IslamicTabular {
+    type Year = i64;
+
+    const NAME: &'static str = "Islamic tabular";
+
+    fn is_leap_year(&self, year: i64) -> bool {
+        (14 + 11 * year).rem_euclid(30) < 11
+    }
+
+    fn months_in_year(&self, _year: i64) -> u8 {
+        12
+    }
+
+    fn days_in_month(&self, year: i64, month: u8) -> u8 {
+        if month % 2 == 1 || (month == 12 && self.is_leap_year(year)) {
+            30
+        } else {
+            29
+        }
+    }
+
+    fn year_number(&self, year: i64) -> i64 {
+        year
+    }
+
+    fn fixed_from_date(&self, year: i64, month: u8, day: u8) -> FixedDay {
+        let month = month as i64;
+        FixedDay(
+            ISLAMIC_EPOCH - 1
+                + (year - 1) * 354
+                + (3 + 11 * year).div_euclid(30)
+                + 29 * (month - 1)
+                + (6 * month - 1) / 11
+                + day as i64,
+        )
+    }
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (i64, u8, u8) {
+        let year = (30 * (fixed.0 - ISLAMIC_EPOCH) + 10_646).div_euclid(10_631);
+        let prior_days = fixed.0 - self.fixed_from_date(year, 1, 1).0;
+        let month = ((11 * prior_days + 330) / 325) as u8;
+        let day = fixed.0 - self.fixed_from_date(year, month, 1).0 + 1;
+        (year, month, day as u8)
+    }
+}
+
+/// The Hebrew calendar, a lunisolar calendar where 7 years out of every 19 have a leap
+/// month. The months are numbered in the civil order, starting with Tishrei at the new year.
+/// Leap years add Adar I as month 6, and the usual Adar becomes Adar II, month 7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hebrew;
+
+/// October 7, 3761 BCE in the Julian calendar.
+const HEBREW_EPOCH: i64 = -1_373_427;
+
+impl Hebrew {
+    pub fn month_name(&self, year: i64, month: u8) -> &'static str {
+        const NAMES: [&str; 13] = [
+            "Nisan", "Iyar", "Sivan", "Tammuz", "Av", "Elul", "Tishrei", "Heshvan", "Kislev",
+            "Tevet", "Shevat", "Adar", "Adar II",
+        ];
+        match self.biblical_month(year, month) {
+            12 if self.is_leap_year(year) => "Adar I",
+            month => NAMES[month as usize - 1],
+        }
+    }
+
+    /// The month number counting from Nisan, the order that the calculations use, where the
+    /// leap month is 13.
+    fn biblical_month(&self, year: i64, month: u8) -> u8 {
+        let months_before_nisan = if self.is_leap_year(year) { 7 } else { 6 };
+        if month <= months_before_nisan {
+            month + 6
+        } else {
+            month - months_before_nisan
+        }
+    }
+
+    fn civil_month(&self, year: i64, biblical_month: u8) -> u8 {
+        let months_before_nisan = if self.is_leap_year(year) { 7 } else { 6 };
+        if biblical_month >= 7 {
+            biblical_month - 6
+        } else {
+            biblical_month + months_before_nisan
+        }
+    }
+
+    /// The days from the epoch to the molad (the mean new moon) of Tishrei, moved forward a
+    /// day when it would put the new year on Sunday, Wednesday or Friday.
+    fn elapsed_days(year: i64) -> i64 {
+        let months_elapsed = (235 * year - 234).div_euclid(19);
+        let parts_elapsed = 12_084 + 13_753 * months_elapsed;
+        let days = 29 * months_elapsed + parts_elapsed.div_euclid(25_920);
+        if (3 * (days + 1)).rem_euclid(7) < 3 {
+            days + 1
+        } else {
+            days
+        }
+    }
+
+    /// Delays the new year further to keep the length of the years within the allowed range.
+    fn year_length_correction(year: i64) -> i64 {
+        let previous = Hebrew::elapsed_days(year - 1);
+        let current = Hebrew::elapsed_days(year);
+        let next = Hebrew::elapsed_days(year + 1);
+        if next - current == 356 {
+            2
+        } else if current - previous == 382 {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn new_year(year: i64) -> i64 {
+        HEBREW_EPOCH + Hebrew::elapsed_days(year) + Hebrew::year_length_correction(year)
+    }
+
+    fn days_in_biblical_month(&self, year: i64, month: u8) -> u8 {
+        let days_in_year = self.days_in_year(year);
+        let is_short = match month {
+            2 | 4 | 6 | 10 | 13 => true,
+            12 => !self.is_leap_year(year),
+            // Heshvan is long in complete years, and Kislev is short in deficient years.
+            8 => days_in_year % 10 != 5,
+            9 => days_in_year % 10 == 3,
+            _ => false,
+        };
+        if is_short {
+            29
+        } else {
+            30
+        }
+    }
+}
+
+impl Calendar for Hebrew {
+    type Year = i64;
+
+    const NAME: &'static str = "Hebrew";
+
+    fn is_leap_year(&self, year: i64) -> bool {
+        (7 * year + 1).rem_euclid(19) < 7
+    }
+
+    fn months_in_year(&self, year: i64) -> u8 {
+        if self.is_leap_year(year) {
+            13
+        } else {
+            12
+        }
+    }
+
+    fn days_in_month(&self, year: i64, month: u8) -> u8 {
+        self.days_in_biblical_month(year, self.biblical_month(year, month))
+    }
+
+    /// Years are 353, 354 or 355 days, or 383, 384 or 385 days in leap years.
+    fn days_in_year(&self, year: i64) -> u16 {
+        (Hebrew::new_year(year + 1) - Hebrew::new_year(year)) as u16
+    }
+
+    fn year_number(&self, year: i64) -> i64 {
+        year
+    }
+
+    fn fixed_from_date(&self, year: i64, month: u8, day: u8) -> FixedDay {
+        let days_before_month: i64 = (1..month)
+            .map(|month| self.days_in_month(year, month) as i64)
+            .sum();
+        FixedDay(Hebrew::new_year(year) + days_before_month + day as i64 - 1)
+    }
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (i64, u8, u8) {
+        // The average length of a year is 35975351 / 98496 days.
+        let approximate = (98_496 * (fixed.0 - HEBREW_EPOCH)).div_euclid(35_975_351) + 1;
+        let mut year = approximate - 1;
+        while Hebrew::new_year(year + 1) <= fixed.0 {
+            year += 1;
+        }
+        let day_of_year = (fixed.0 - Hebrew::new_year(year) + 1) as u16;
+        let (month, day) = self.day_month(year, day_of_year);
+        (year, month, day)
+    }
+}
+
+/// The Japanese calendar, which is the Gregorian calendar with the years counted by the era
+/// of the emperor. Only the modern eras are supported, and dates before the Meiji era count
+/// back from it, so 1867 is Meiji 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Japanese;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Era {
+    Meiji,
+    Taisho,
+    Showa,
+    Heisei,
+    Reiwa,
+}
+
+impl Era {
+    const ALL: [Era; 5] = [Era::Meiji, Era::Taisho, Era::Showa, Era::Heisei, Era::Reiwa];
+
+    /// The Gregorian date of the first day of the era.
+    pub const fn start(&self) -> (i64, Month, u8) {
+        match self {
+            Era::Meiji => (1868, Month::September, 8),
+            Era::Taisho => (1912, Month::July, 30),
+            Era::Showa => (1926, Month::December, 25),
+            Era::Heisei => (1989, Month::January, 8),
+            Era::Reiwa => (2019, Month::May, 1),
+        }
+    }
+
+    const fn start_fixed(&self) -> FixedDay {
+        let (year, month, day) = self.start();
+        FixedDay(days_from_civil(year, month, day) + UNIX_EPOCH_FIXED)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JapaneseYear {
+    pub era: Era,
+    /// The first year of the era is 1.
+    pub year: i64,
+}
+
+impl Japanese {
+    fn gregorian_year(year: JapaneseYear) -> i64 {
+        year.era.start().0 + year.year - 1
+    }
+}
+
+impl Calendar for Japanese {
+    type Year = JapaneseYear;
+
+    const NAME: &'static str = "Japanese";
+
+    fn is_leap_year(&self, year: JapaneseYear) -> bool {
+        Gregorian.is_leap_year(Japanese::gregorian_year(year))
+    }
+
+    fn months_in_year(&self, _year: JapaneseYear) -> u8 {
+        12
+    }
+
+    fn days_in_month(&self, year: JapaneseYear, month: u8) -> u8 {
+        Gregorian.days_in_month(Japanese::gregorian_year(year), month)
+    }
+
+    fn year_number(&self, year: JapaneseYear) -> i64 {
+        year.year
+    }
+
+    fn fixed_from_date(&self, year: JapaneseYear, month: u8, day: u8) -> FixedDay {
+        Gregorian.fixed_from_date(Japanese::gregorian_year(year), month, day)
+    }
+
+    fn date_from_fixed(&self, fixed: FixedDay) -> (JapaneseYear, u8, u8) {
+        let era = Era::ALL
+            .into_iter()
+            .rev()
+            .find(|era| era.start_fixed() <= fixed)
+            .unwrap_or(Era::Meiji);
+        let (year, month, day) = Gregorian.date_from_fixed(fixed);
+        let year = JapaneseYear {
+            era,
+            year: year - era.start().0 + 1,
+        };
+        (year, month, day)
+    }
+}
+
+#[test]
+fn test_fixed_day() {
+    assert_eq!(FixedDay::from(EpochMS(0)), FixedDay(UNIX_EPOCH_FIXED));
+    assert_eq!(FixedDay(1).epoch(), EpochMS(-62_135_596_800_000));
+    assert_eq!(Gregorian.fixed_from_date(1, 1, 1), FixedDay(1));
+    assert_eq!(Gregorian.fixed_from_date(0, 12, 30), FixedDay(JULIAN_EPOCH));
+    assert_eq!(Julian.fixed_from_date(622, 7, 16), FixedDay(ISLAMIC_EPOCH));
+    assert_eq!(Julian.fixed_from_date(-3760, 10, 7), FixedDay(HEBREW_EPOCH));
+}
+
+#[test]
+fn test_calendar_conversions() {
+    // Sample dates from "Calendrical Calculations", with the Hebrew months in civil order.
+    let cases = [
+        (
+            -214_193,
+            (-586, 7, 24),
+            (-586, 7, 30),
+            (-1245, 12, 9),
+            (3174, 11, 10),
+            (-586, 29, 7),
+        ),
+        (
+            601_716,
+            (1648, 6, 10),
+            (1648, 5, 31),
+            (1058, 5, 18),
+            (5408, 9, 20),
+            (1648, 24, 3),
+        ),
+        (
+            710_347,
+            (1945, 11, 12),
+            (1945, 10, 30),
+            (1364, 12, 6),
+            (5706, 3, 7),
+            (1945, 46, 1),
+        ),
+        (
+            764_652,
+            (2094, 7, 18),
+            (2094, 7, 5),
+            (1518, 3, 5),
+            (5854, 11, 5),
+            (2094, 28, 7),
+        ),
+    ];
+    fn check<C: Calendar<Year = i64>>(calendar: C, fixed: i64, (year, month, day): (i64, u8, u8)) {
+        let date = CalendarDate::new(calendar, year, month, day).unwrap();
+        assert_eq!(date.fixed(), FixedDay(fixed), "{} {:?}", C::NAME, date);
+        assert_eq!(CalendarDate::from_fixed(calendar, FixedDay(fixed)), date);
+    }
+    for (fixed, gregorian, julian, islamic, hebrew, iso) in cases {
+        check(Gregorian, fixed, gregorian);
+        check(Julian, fixed, julian);
+        check(IslamicTabular, fixed, islamic);
+        check(Hebrew, fixed, hebrew);
+        check(Iso, fixed, iso);
+    }
+
+    let date = Date::new(2021, Month::February, 1, 17, 19, 5, 123).unwrap();
+    let julian = date.to_calendar(Julian);
+    assert_eq!((julian.year, julian.month, julian.day), (2021, 1, 19));
+    let hebrew = julian.to_calendar(Hebrew);
+    assert_eq!((hebrew.year, hebrew.month, hebrew.day), (5781, 5, 19));
+    assert_eq!(hebrew.calendar.month_name(5781, 5), "Shevat");
+    assert_eq!(
+        Date::from(hebrew),
+        Date::new(2021, Month::February, 1, 0, 0, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn test_calendar_round_trip() {
+    fn check<C: Calendar>(calendar: C) {
+        let mut previous = CalendarDate::from_fixed(calendar, FixedDay(-2_000));
+        for fixed in -1_999..2_000 {
+            let date = CalendarDate::from_fixed(calendar, FixedDay(fixed));
+            assert_eq!(date.fixed(), FixedDay(fixed), "{} {:?}", C::NAME, date);
+            assert_eq!(
+                CalendarDate::new(calendar, date.year, date.month, date.day),
+                Ok(date)
+            );
+            if date.year == previous.year {
+                assert!((date.month, date.day) > (previous.month, previous.day));
+            }
+            previous = date;
+        }
+        // Then a few days every 8 years or so, out to about the year 20,000 either way.
+        for fixed in (-7_300_000..7_300_000).step_by(3_001) {
+            let date = CalendarDate::from_fixed(calendar, FixedDay(fixed));
+            assert_eq!(date.fixed(), FixedDay(fixed), "{} {:?}", C::NAME, date);
+        }
+    }
+    check(Gregorian);
+    check(Julian);
+    check(Iso);
+    check(IslamicTabular);
+    check(Hebrew);
+    check(Japanese);
+}
+
+#[test]
+fn test_hebrew_calendar() {
+    // Rosh Hashanah, and the first day of Passover in a leap year.
+    let date = Date::new(2024, Month::October, 3, 0, 0, 0, 0).unwrap();
+    let hebrew = date.to_calendar(Hebrew);
+    assert_eq!((hebrew.year, hebrew.month, hebrew.day), (5785, 1, 1));
+    let passover = CalendarDate::new(Hebrew, 5784, 8, 15).unwrap();
+    assert_eq!(
+        Date::from(passover),
+        Date::new(2024, Month::April, 23, 0, 0, 0, 0).unwrap()
+    );
+
+    assert!(Hebrew.is_leap_year(5784));
+    assert_eq!(Hebrew.months_in_year(5784), 13);
+    assert_eq!(Hebrew.month_name(5784, 6), "Adar I");
+    assert_eq!(Hebrew.month_name(5784, 7), "Adar II");
+    assert_eq!(Hebrew.month_name(5785, 6), "Adar");
+    assert_eq!(Hebrew.month_name(5785, 7), "Nisan");
+    for year in 5700..5800 {
+        let days = Hebrew.days_in_year(year);
+        assert!(
+            [353, 354, 355, 383, 384, 385].contains(&days),
+            "{} has {} days",
+            year,
+            days
+        );
+    }
+    assert_eq!(
+        CalendarDate::new(Hebrew, 5785, 13, 1),
+        Err(DateError::InvalidCalendarDate {
+            calendar: "Hebrew",
+            year: 5785,
+            month: 13,
+            day: 1
+        })
+    );
+}
+
+#[test]
+fn test_japanese_calendar() {
+    let reiwa = |year| JapaneseYear {
+        era: Era::Reiwa,
+        year,
+    };
+    let heisei = |year| JapaneseYear {
+        era: Era::Heisei,
+        year,
+    };
+    let date = Date::new(2019, Month::May, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        date.to_calendar(Japanese),
+        CalendarDate::new(Japanese, reiwa(1), 5, 1).unwrap()
+    );
+    assert_eq!(
+        (date - std::time::Duration::from_secs(1)).to_calendar(Japanese),
+        CalendarDate::new(Japanese, heisei(31), 4, 30).unwrap()
+    );
+    assert_eq!(
+        CalendarDate::new(Japanese, reiwa(1), 4, 30),
+        Err(DateError::InvalidCalendarDate {
+            calendar: "Japanese",
+            year: 1,
+            month: 4,
+            day: 30
+        })
+    );
+    assert!(CalendarDate::new(Japanese, heisei(31), 5, 1).is_err());
+    assert!(CalendarDate::new(Japanese, heisei(2), 2, 29).is_err());
+    assert!(CalendarDate::new(Japanese, heisei(4), 2, 29).is_ok());
+
+    let date = Date::new(1867, Month::January, 1, 0, 0, 0, 0).unwrap();
+    let japanese = date.to_calendar(Japanese);
+    assert_eq!(
+        japanese.year,
+        JapaneseYear {
+            era: Era::Meiji,
+            year: 0
+        }
+    );
+}
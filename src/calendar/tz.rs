@@ -0,0 +1,779 @@
+//! Time zones, read from the compiled TZif files of the IANA time zone database, as described
+//! in RFC 8536. These live in `/usr/share/zoneinfo` on most systems, and there is a small
+//! vendored copy in `data/zoneinfo` for the tests.
+//!
+//! A TZif file lists every historical transition between local time types, e.g. from EST to
+//! EDT. Newer files end with a POSIX TZ string like `EST5EDT,M3.2.0,M11.1.0`, which is the
+//! rule for every transition after the last one that is listed.
+
+use super::systems::{Calendar, Gregorian};
+use super::{civil_from_days, days_from_civil, Date, EpochMS, Month, Weekday};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+
+#[derive(Debug)]
+pub enum TzError {
+    /// The name must be a relative path inside of the directory, like `America/New_York`.
+    InvalidName(String),
+    Io(io::Error),
+    /// The TZif data is malformed.
+    InvalidData(&'static str),
+    /// The POSIX TZ string at the end of the file couldn't be parsed.
+    InvalidRule(String),
+}
+
+impl fmt::Display for TzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TzError::InvalidName(name) => write!(f, "{:?} is not a valid time zone name", name),
+            TzError::Io(error) => write!(f, "unable to read the time zone: {}", error),
+            TzError::InvalidData(reason) => write!(f, "invalid TZif data: {}", reason),
+            TzError::InvalidRule(rule) => write!(f, "invalid POSIX TZ string {:?}", rule),
+        }
+    }
+}
+
+impl std::error::Error for TzError {}
+
+impl From<io::Error> for TzError {
+    fn from(error: io::Error) -> TzError {
+        TzError::Io(error)
+    }
+}
+
+/// The offset from UTC and its name, e.g. EDT is -4 hours and daylight saving time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalTimeType {
+    /// Seconds east of UTC.
+    pub utc_offset: i32,
+    pub is_dst: bool,
+    pub abbreviation: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    name: String,
+    /// The UTC seconds at which the local time type changes, in order, along with the index
+    /// of the new type in `local_types`.
+    transitions: Vec<(i64, usize)>,
+    local_types: Vec<LocalTimeType>,
+    /// The rule for everything after the last transition.
+    rule: Option<PosixRule>,
+}
+
+/// A date in the local time of a time zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZonedDate {
+    /// The wall clock fields. Its timestamp is shifted by the offset, so use `epoch` for
+    /// the actual instant.
+    pub date: Date,
+    pub local_time_type: LocalTimeType,
+    pub epoch: EpochMS,
+}
+
+/// Converting a local time to UTC doesn't always have exactly one answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalResult {
+    Single(ZonedDate),
+    /// The local time happens twice, as the clocks were turned back. The earlier instant is
+    /// first.
+    Ambiguous(ZonedDate, ZonedDate),
+    /// The local time never happened, as the clocks jumped forward over it. This is the
+    /// instant when using the offset from before the jump, which reads as the local time
+    /// pushed forward by the length of the gap, e.g. 2:30 AM becomes 3:30 AM.
+    Skipped(ZonedDate),
+}
+
+impl TimeZone {
+    /// Read a time zone by its name from a zoneinfo directory, e.g.
+    /// `TimeZone::from_directory("/usr/share/zoneinfo", "America/New_York")`.
+    pub fn from_directory(directory: impl AsRef<Path>, name: &str) -> Result<TimeZone, TzError> {
+        let path = Path::new(name);
+        let is_relative = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if name.is_empty() || !is_relative {
+            return Err(TzError::InvalidName(name.into()));
+        }
+        let bytes = fs::read(directory.as_ref().join(path))?;
+        TimeZone::parse(name, &bytes)
+    }
+
+    /// Parse the contents of a TZif file.
+    pub fn parse(name: &str, bytes: &[u8]) -> Result<TimeZone, TzError> {
+        let mut reader = Reader { bytes };
+        let header = Header::read(&mut reader)?;
+        let (mut time_zone, footer) = if header.version == 0 {
+            (header.read_block(&mut reader, 4)?, None)
+        } else {
+            // Version 2 and later repeat the data with 64 bit times, which is the only part
+            // that needs reading.
+            reader.take(header.block_len(&reader, 4)?)?;
+            let header = Header::read(&mut reader)?;
+            let time_zone = header.read_block(&mut reader, 8)?;
+            (time_zone, Some(reader.footer()?))
+        };
+        time_zone.name = name.into();
+        time_zone.rule = match footer {
+            Some(footer) if !footer.is_empty() => Some(PosixRule::parse(footer)?),
+            _ => None,
+        };
+        Ok(time_zone)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The offset and abbreviation in effect at an instant.
+    pub fn local_time_type(&self, epoch: EpochMS) -> LocalTimeType {
+        let seconds = epoch.seconds_since();
+        // The index of the first transition after this instant.
+        let next = self.transitions.partition_point(|&(at, _)| at <= seconds);
+        if next == self.transitions.len() {
+            if let Some(rule) = &self.rule {
+                return rule.local_time_type(seconds);
+            }
+        }
+        match next {
+            // Before the first transition, the first type is used.
+            0 => self.local_types[0].clone(),
+            next => self.local_types[self.transitions[next - 1].1].clone(),
+        }
+    }
+
+    /// View an instant in this time zone.
+    pub fn zoned(&self, epoch: EpochMS) -> ZonedDate {
+        let local_time_type = self.local_time_type(epoch);
+        ZonedDate {
+            date: Date::from(EpochMS(epoch.0 + local_time_type.utc_offset as i64 * 1000)),
+            local_time_type,
+            epoch,
+        }
+    }
+
+    /// Find the instant, or instants, for a wall clock time in this time zone. The timestamp
+    /// of the `local` date is ignored, only its fields are used.
+    pub fn resolve(&self, local: &Date) -> LocalResult {
+        let local_ms = local.timestamp;
+        // Transitions are never within a day of each other, so the offsets from a day
+        // either side cover every possibility.
+        let day_ms = 86_400_000;
+        let before = self.local_time_type(EpochMS(local_ms - day_ms)).utc_offset;
+        let after = self.local_time_type(EpochMS(local_ms + day_ms)).utc_offset;
+
+        let mut candidates = Vec::new();
+        for offset in [before, after] {
+            let epoch = EpochMS(local_ms - offset as i64 * 1000);
+            if self.local_time_type(epoch).utc_offset == offset && !candidates.contains(&epoch) {
+                candidates.push(epoch);
+            }
+        }
+        candidates.sort();
+
+        match candidates[..] {
+            [epoch] => LocalResult::Single(self.zoned(epoch)),
+            [earlier, later] => LocalResult::Ambiguous(self.zoned(earlier), self.zoned(later)),
+            _ => LocalResult::Skipped(self.zoned(EpochMS(local_ms - before as i64 * 1000))),
+        }
+    }
+}
+
+impl fmt::Display for ZonedDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Sunday, March 14, 2021 03:00:00:000 EDT
+        write!(f, "{} {}", self.date, self.local_time_type.abbreviation)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TzError> {
+        if self.bytes.len() < len {
+            return Err(TzError::InvalidData("the file ended unexpectedly"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, TzError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, TzError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Times are 32 bits in the version 1 data, and 64 bits afterwards.
+    fn time(&mut self, size: usize) -> Result<i64, TzError> {
+        let bytes = self.take(size)?;
+        Ok(match size {
+            4 => i32::from_be_bytes(bytes.try_into().unwrap()) as i64,
+            _ => i64::from_be_bytes(bytes.try_into().unwrap()),
+        })
+    }
+
+    /// The footer is a POSIX TZ string between two newlines.
+    fn footer(&mut self) -> Result<&'a str, TzError> {
+        if self.u8()? != b'\n' {
+            return Err(TzError::InvalidData("the footer must start with a newline"));
+        }
+        let end = self
+            .bytes
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or(TzError::InvalidData("the footer must end with a newline"))?;
+        std::str::from_utf8(self.take(end)?)
+            .map_err(|_| TzError::InvalidData("the footer is not valid UTF-8"))
+    }
+}
+
+struct Header {
+    version: u8,
+    is_ut_count: usize,
+    is_std_count: usize,
+    leap_count: usize,
+    time_count: usize,
+    type_count: usize,
+    char_count: usize,
+}
+
+impl Header {
+    fn read(reader: &mut Reader) -> Result<Header, TzError> {
+        if reader.take(4)? != b"TZif" {
+            return Err(TzError::InvalidData("the magic number is missing"));
+        }
+        let version = match reader.u8()? {
+            0 => 0,
+            version @ b'2'..=b'9' => version - b'0',
+            _ => return Err(TzError::InvalidData("unknown version")),
+        };
+        reader.take(15)?;
+        let header = Header {
+            version,
+            is_ut_count: reader.u32()? as usize,
+            is_std_count: reader.u32()? as usize,
+            leap_count: reader.u32()? as usize,
+            time_count: reader.u32()? as usize,
+            type_count: reader.u32()? as usize,
+            char_count: reader.u32()? as usize,
+        };
+        if header.type_count == 0 {
+            return Err(TzError::InvalidData(
+                "there must be at least one local time type",
+            ));
+        }
+        Ok(header)
+    }
+
+    /// The length of the data that follows the header. The counts come from the file, so
+    /// this checks that there is that much left before anything is allocated for them.
+    fn block_len(&self, reader: &Reader, time_size: usize) -> Result<usize, TzError> {
+        [
+            (self.time_count, time_size + 1),
+            (self.type_count, 6),
+            (self.char_count, 1),
+            (self.leap_count, time_size + 4),
+            (self.is_std_count, 1),
+            (self.is_ut_count, 1),
+        ]
+        .into_iter()
+        .try_fold(0_usize, |len, (count, size)| {
+            len.checked_add(count.checked_mul(size)?)
+        })
+        .filter(|&len| len <= reader.bytes.len())
+        .ok_or(TzError::InvalidData(
+            "the file is shorter than its header says",
+        ))
+    }
+
+    fn read_block(&self, reader: &mut Reader, time_size: usize) -> Result<TimeZone, TzError> {
+        self.block_len(reader, time_size)?;
+        let mut times = Vec::with_capacity(self.time_count);
+        for _ in 0..self.time_count {
+            times.push(reader.time(time_size)?);
+        }
+        let mut transitions = Vec::with_capacity(self.time_count);
+        for at in times {
+            let index = reader.u8()? as usize;
+            if index >= self.type_count {
+                return Err(TzError::InvalidData("a transition has an unknown type"));
+            }
+            transitions.push((at, index));
+        }
+
+        let mut raw_types = Vec::with_capacity(self.type_count);
+        for _ in 0..self.type_count {
+            let utc_offset = reader.time(4)? as i32;
+            let is_dst = reader.u8()? != 0;
+            let abbreviation_index = reader.u8()? as usize;
+            raw_types.push((utc_offset, is_dst, abbreviation_index));
+        }
+        let chars = reader.take(self.char_count)?;
+        let mut local_types = Vec::with_capacity(self.type_count);
+        for (utc_offset, is_dst, abbreviation_index) in raw_types {
+            let rest = chars
+                .get(abbreviation_index..)
+                .ok_or(TzError::InvalidData("an abbreviation is out of bounds"))?;
+            let end = rest
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(rest.len());
+            local_types.push(LocalTimeType {
+                utc_offset,
+                is_dst,
+                abbreviation: String::from_utf8_lossy(&rest[..end]).into_owned(),
+            });
+        }
+
+        // The leap second records and standard/UT indicators aren't needed to find offsets.
+        reader.take(self.leap_count * (time_size + 4) + self.is_std_count + self.is_ut_count)?;
+
+        Ok(TimeZone {
+            name: String::new(),
+            transitions,
+            local_types,
+            rule: None,
+        })
+    }
+}
+
+/// A POSIX TZ string, like `EST5EDT,M3.2.0,M11.1.0`. Note that POSIX offsets are west of
+/// UTC, so EST5 is 5 hours behind UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PosixRule {
+    std: LocalTimeType,
+    dst: Option<DstRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DstRule {
+    dst: LocalTimeType,
+    /// When DST starts, in the local standard time.
+    start: (RuleDate, i32),
+    /// When DST ends, in the local daylight saving time.
+    end: (RuleDate, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDate {
+    /// `Jn`, the day of the year from 1 to 365, where February 29 is never counted.
+    Julian(u16),
+    /// `n`, the day of the year from 0 to 365, where February 29 is counted.
+    ZeroBased(u16),
+    /// `Mm.w.d`, the `d`th weekday (Sunday is 0) of the `w`th week of the month, where the
+    /// week 5 means the last one.
+    MonthWeekDay(Month, u8, u8),
+}
+
+impl PosixRule {
+    fn parse(text: &str) -> Result<PosixRule, TzError> {
+        let invalid = || TzError::InvalidRule(text.into());
+        let mut parser = RuleParser { text, index: 0 };
+
+        let std_name = parser.name().ok_or_else(invalid)?;
+        let std_offset = -parser.time().ok_or_else(invalid)?;
+        let std = LocalTimeType {
+            utc_offset: std_offset,
+            is_dst: false,
+            abbreviation: std_name,
+        };
+        if parser.is_done() {
+            return Ok(PosixRule { std, dst: None });
+        }
+
+        let dst_name = parser.name().ok_or_else(invalid)?;
+        let dst_offset = if parser.peek() == Some(b',') || parser.is_done() {
+            std_offset + 3600
+        } else {
+            -parser.time().ok_or_else(invalid)?
+        };
+        // POSIX leaves the default rule up to the implementation, so use the US rules.
+        let (start, end) = if parser.is_done() {
+            (
+                (RuleDate::MonthWeekDay(Month::March, 2, 0), 7200),
+                (RuleDate::MonthWeekDay(Month::November, 1, 0), 7200),
+            )
+        } else {
+            parser.expect(b',').ok_or_else(invalid)?;
+            let start = parser.rule_date_time().ok_or_else(invalid)?;
+            parser.expect(b',').ok_or_else(invalid)?;
+            let end = parser.rule_date_time().ok_or_else(invalid)?;
+            (start, end)
+        };
+        if !parser.is_done() {
+            return Err(invalid());
+        }
+
+        Ok(PosixRule {
+            std,
+            dst: Some(DstRule {
+                dst: LocalTimeType {
+                    utc_offset: dst_offset,
+                    is_dst: true,
+                    abbreviation: dst_name,
+                },
+                start,
+                end,
+            }),
+        })
+    }
+
+    fn local_time_type(&self, seconds: i64) -> LocalTimeType {
+        let rule = match &self.dst {
+            Some(rule) => rule,
+            None => return self.std.clone(),
+        };
+        let local_days = (seconds + self.std.utc_offset as i64).div_euclid(86_400);
+        let (year, _, _) = civil_from_days(local_days);
+
+        let start =
+            rule.start.0.days(year) * 86_400 + rule.start.1 as i64 - self.std.utc_offset as i64;
+        let end = rule.end.0.days(year) * 86_400 + rule.end.1 as i64 - rule.dst.utc_offset as i64;
+        let is_dst = if start < end {
+            start <= seconds && seconds < end
+        } else {
+            // The southern hemisphere, where DST spans the new year.
+            !(end <= seconds && seconds < start)
+        };
+        if is_dst {
+            rule.dst.clone()
+        } else {
+            self.std.clone()
+        }
+    }
+}
+
+impl RuleDate {
+    /// The days since 1970 for this date in the given year.
+    fn days(&self, year: i64) -> i64 {
+        let is_leap_year = Gregorian.is_leap_year(year);
+        let january_1 = days_from_civil(year, Month::January, 1);
+        match *self {
+            RuleDate::Julian(day) => {
+                let leap_day = if is_leap_year && day >= 60 { 1 } else { 0 };
+                january_1 + day as i64 - 1 + leap_day
+            }
+            RuleDate::ZeroBased(day) => january_1 + day as i64,
+            RuleDate::MonthWeekDay(month, week, weekday) => {
+                let first = days_from_civil(year, month, 1);
+                let first_weekday = Weekday::from(EpochMS(first * 86_400_000)).number_from_sunday();
+                let mut day =
+                    (weekday as i64 - first_weekday as i64).rem_euclid(7) + (week as i64 - 1) * 7;
+                while day >= month.days(is_leap_year) as i64 {
+                    day -= 7;
+                }
+                first + day
+            }
+        }
+    }
+}
+
+struct RuleParser<'a> {
+    text: &'a str,
+    index: usize,
+}
+
+impl RuleParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.index).copied()
+    }
+
+    fn is_done(&self) -> bool {
+        self.index == self.text.len()
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        if self.peek() == Some(byte) {
+            self.index += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Either 3 or more letters like `EST`, or anything quoted with angle brackets like
+    /// `<+0330>`.
+    fn name(&mut self) -> Option<String> {
+        let start = self.index;
+        if self.expect(b'<').is_some() {
+            while self.peek()? != b'>' {
+                self.index += 1;
+            }
+            self.index += 1;
+            return Some(self.text[start + 1..self.index - 1].into());
+        }
+        while matches!(self.peek(), Some(byte) if byte.is_ascii_alphabetic()) {
+            self.index += 1;
+        }
+        if self.index - start < 3 {
+            return None;
+        }
+        Some(self.text[start..self.index].into())
+    }
+
+    fn number(&mut self) -> Option<i32> {
+        let start = self.index;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.index += 1;
+        }
+        self.text[start..self.index].parse().ok()
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds.
+    fn time(&mut self) -> Option<i32> {
+        let sign = match self.peek() {
+            Some(b'-') => {
+                self.index += 1;
+                -1
+            }
+            Some(b'+') => {
+                self.index += 1;
+                1
+            }
+            _ => 1,
+        };
+        let mut seconds = self.number()?.checked_mul(3600)?;
+        if self.expect(b':').is_some() {
+            seconds = seconds.checked_add(self.number()?.checked_mul(60)?)?;
+            if self.expect(b':').is_some() {
+                seconds = seconds.checked_add(self.number()?)?;
+            }
+        }
+        Some(sign * seconds)
+    }
+
+    /// A rule date with an optional time, which defaults to 02:00:00.
+    fn rule_date_time(&mut self) -> Option<(RuleDate, i32)> {
+        let date = match self.peek()? {
+            b'J' => {
+                self.index += 1;
+                let day = self.number()?;
+                if !(1..=365).contains(&day) {
+                    return None;
+                }
+                RuleDate::Julian(day as u16)
+            }
+            b'M' => {
+                self.index += 1;
+                let month = Month::try_from(u8::try_from(self.number()?).ok()?).ok()?;
+                self.expect(b'.')?;
+                let week = self.number()?;
+                self.expect(b'.')?;
+                let weekday = self.number()?;
+                if !(1..=5).contains(&week) || !(0..=6).contains(&weekday) {
+                    return None;
+                }
+                RuleDate::MonthWeekDay(month, week as u8, weekday as u8)
+            }
+            _ => {
+                let day = self.number()?;
+                if day > 365 {
+                    return None;
+                }
+                RuleDate::ZeroBased(day as u16)
+            }
+        };
+        let time = if self.expect(b'/').is_some() {
+            self.time()?
+        } else {
+            7200
+        };
+        Some((date, time))
+    }
+}
+
+#[cfg(test)]
+fn get_zone(name: &str) -> TimeZone {
+    TimeZone::from_directory("data/zoneinfo", name).expect("Unable to load the time zone.")
+}
+
+#[test]
+fn test_new_york() {
+    let zone = get_zone("America/New_York");
+    assert_eq!(zone.name(), "America/New_York");
+
+    // The clocks jump forward at 2:00 AM EST, which is 07:00 UTC.
+    let before = Date::new(2021, Month::March, 14, 6, 59, 59, 0).unwrap();
+    let after = Date::new(2021, Month::March, 14, 7, 0, 0, 0).unwrap();
+    assert_eq!(
+        zone.zoned(before.epoch()).to_string(),
+        "Sunday, March 14, 2021 01:59:59:000 EST"
+    );
+    assert_eq!(
+        zone.zoned(after.epoch()).to_string(),
+        "Sunday, March 14, 2021 03:00:00:000 EDT"
+    );
+    assert_eq!(
+        zone.local_time_type(after.epoch()),
+        LocalTimeType {
+            utc_offset: -4 * 3600,
+            is_dst: true,
+            abbreviation: "EDT".into()
+        }
+    );
+
+    // Before the first transition, local mean time was in use.
+    let date = Date::new(1850, Month::January, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(zone.local_time_type(date.epoch()).abbreviation, "LMT");
+}
+
+#[test]
+fn test_rules_after_the_last_transition() {
+    let zone = get_zone("America/New_York");
+    // The second Sunday of March, 2050 is the 13th.
+    let before = Date::new(2050, Month::March, 13, 6, 59, 59, 999).unwrap();
+    let after = Date::new(2050, Month::March, 13, 7, 0, 0, 0).unwrap();
+    assert_eq!(zone.local_time_type(before.epoch()).abbreviation, "EST");
+    assert_eq!(zone.local_time_type(after.epoch()).abbreviation, "EDT");
+
+    // The first Sunday of November, 2050 is the 6th, at 2:00 AM EDT.
+    let before = Date::new(2050, Month::November, 6, 5, 59, 59, 999).unwrap();
+    let after = Date::new(2050, Month::November, 6, 6, 0, 0, 0).unwrap();
+    assert_eq!(zone.local_time_type(before.epoch()).abbreviation, "EDT");
+    assert_eq!(zone.local_time_type(after.epoch()).abbreviation, "EST");
+
+    // DST in the southern hemisphere spans the new year.
+    let zone = get_zone("Australia/Sydney");
+    let summer = Date::new(2050, Month::January, 15, 0, 0, 0, 0).unwrap();
+    let winter = Date::new(2050, Month::June, 15, 0, 0, 0, 0).unwrap();
+    assert_eq!(zone.local_time_type(summer.epoch()).utc_offset, 11 * 3600);
+    assert_eq!(zone.local_time_type(winter.epoch()).utc_offset, 10 * 3600);
+
+    // London switches at 01:00 UTC on the last Sunday of March, the 27th in 2050.
+    let zone = get_zone("Europe/London");
+    let before = Date::new(2050, Month::March, 27, 0, 59, 59, 0).unwrap();
+    let after = Date::new(2050, Month::March, 27, 1, 0, 0, 0).unwrap();
+    assert_eq!(
+        zone.zoned(before.epoch()).to_string(),
+        "Sunday, March 27, 2050 00:59:59:000 GMT"
+    );
+    assert_eq!(
+        zone.zoned(after.epoch()).to_string(),
+        "Sunday, March 27, 2050 02:00:00:000 BST"
+    );
+
+    let zone = get_zone("Asia/Kolkata");
+    let date = Date::new(2050, Month::March, 27, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        zone.local_time_type(date.epoch()).utc_offset,
+        5 * 3600 + 1800
+    );
+
+    let zone = get_zone("UTC");
+    assert_eq!(zone.local_time_type(date.epoch()).abbreviation, "UTC");
+}
+
+#[test]
+fn test_resolve_local_times() {
+    let zone = get_zone("America/New_York");
+
+    let local = Date::new(2021, Month::July, 1, 12, 0, 0, 0).unwrap();
+    let utc = Date::new(2021, Month::July, 1, 16, 0, 0, 0).unwrap();
+    assert_eq!(
+        zone.resolve(&local),
+        LocalResult::Single(zone.zoned(utc.epoch()))
+    );
+
+    // 2:30 AM never happened on March 14, 2021.
+    let local = Date::new(2021, Month::March, 14, 2, 30, 0, 0).unwrap();
+    match zone.resolve(&local) {
+        LocalResult::Skipped(zoned) => {
+            assert_eq!(zoned.to_string(), "Sunday, March 14, 2021 03:30:00:000 EDT")
+        }
+        result => panic!("Expected a skipped time, got {:?}", result),
+    }
+
+    // 1:30 AM happened twice on November 7, 2021.
+    let local = Date::new(2021, Month::November, 7, 1, 30, 0, 0).unwrap();
+    match zone.resolve(&local) {
+        LocalResult::Ambiguous(earlier, later) => {
+            assert_eq!(
+                earlier.to_string(),
+                "Sunday, November 7, 2021 01:30:00:000 EDT"
+            );
+            assert_eq!(
+                later.to_string(),
+                "Sunday, November 7, 2021 01:30:00:000 EST"
+            );
+            assert_eq!(later.epoch.0 - earlier.epoch.0, 3_600_000);
+        }
+        result => panic!("Expected an ambiguous time, got {:?}", result),
+    }
+
+    // The same applies when the transitions come from the footer rule.
+    let local = Date::new(2050, Month::March, 13, 2, 30, 0, 0).unwrap();
+    assert!(matches!(zone.resolve(&local), LocalResult::Skipped(_)));
+}
+
+#[test]
+fn test_posix_rules() {
+    let rule = PosixRule::parse("<+0330>-3:30").unwrap();
+    assert_eq!(rule.std.abbreviation, "+0330");
+    assert_eq!(rule.std.utc_offset, 3 * 3600 + 1800);
+    assert_eq!(rule.dst, None);
+
+    let rule = PosixRule::parse("AAA3BBB,J60/2,300/-1").unwrap();
+    let dst = rule.dst.unwrap();
+    assert_eq!(dst.dst.utc_offset, -2 * 3600);
+    assert_eq!(dst.start, (RuleDate::Julian(60), 7200));
+    assert_eq!(dst.end, (RuleDate::ZeroBased(300), -3600));
+    // J60 is always March 1, but the zero based 59 is February 29 in a leap year.
+    assert_eq!(
+        RuleDate::Julian(60).days(2020),
+        days_from_civil(2020, Month::March, 1)
+    );
+    assert_eq!(
+        RuleDate::ZeroBased(59).days(2020),
+        days_from_civil(2020, Month::February, 29)
+    );
+
+    assert!(PosixRule::parse("EST5EDT,M3.2.0").is_err());
+    assert!(PosixRule::parse("E5").is_err());
+    assert!(PosixRule::parse("EST5EDT,M13.2.0,M11.1.0").is_err());
+    // Neither of these wrap around to a valid rule.
+    assert!(PosixRule::parse("EST600000").is_err());
+    assert!(PosixRule::parse("EST5EDT,M257.2.0,M11.1.0").is_err());
+}
+
+#[test]
+fn test_errors() {
+    for name in ["../zoneinfo/UTC", "/etc/localtime", ""] {
+        assert!(matches!(
+            TimeZone::from_directory("data/zoneinfo", name),
+            Err(TzError::InvalidName(_))
+        ));
+    }
+    assert!(matches!(
+        TimeZone::from_directory("data/zoneinfo", "Mars/Olympus_Mons"),
+        Err(TzError::Io(_))
+    ));
+    assert!(matches!(
+        TimeZone::parse("Nope", b"nope"),
+        Err(TzError::InvalidData(_))
+    ));
+
+    let bytes = fs::read("data/zoneinfo/America/New_York").unwrap();
+    assert!(matches!(
+        TimeZone::parse("Truncated", &bytes[..bytes.len() / 2]),
+        Err(TzError::InvalidData(_))
+    ));
+
+    // A header that claims billions of transitions is rejected before they are allocated.
+    let mut bytes = b"TZif\0".to_vec();
+    bytes.extend([0; 15]);
+    for count in [0, 0, 0, u32::MAX, 1, 0] {
+        bytes.extend(count.to_be_bytes());
+    }
+    assert!(matches!(
+        TimeZone::parse("Huge", &bytes),
+        Err(TzError::InvalidData(_))
+    ));
+}
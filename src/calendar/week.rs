@@ -0,0 +1,244 @@
+//! Ordinal dates (the day of the year), and week dates, such as the ISO 8601 week date
+//! `2021-W05-1`. Week numbering depends on a [WeekRule]: which day the week starts on, and
+//! how many days of the new year the first week needs. ISO 8601 weeks start on Monday, and
+//! week 1 is the one with the first Thursday in it, so the first 3 days of January can fall
+//! in the last week of the previous year.
+
+use super::systems::{Calendar, Gregorian};
+use super::{civil_from_days, days_from_civil, Date, DateError, EpochMS, Month, Weekday};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekRule {
+    pub first_day: Weekday,
+    /// How many days of the new year must be in the first week, from 1 to 7.
+    pub minimal_days: u8,
+}
+
+impl WeekRule {
+    /// ISO 8601 weeks, which are also the norm in most of Europe.
+    pub const ISO: WeekRule = WeekRule {
+        first_day: Weekday::Monday,
+        minimal_days: 4,
+    };
+
+    /// Weeks in the US start on Sunday, and week 1 is the one with January 1 in it.
+    pub const US: WeekRule = WeekRule {
+        first_day: Weekday::Sunday,
+        minimal_days: 1,
+    };
+
+    /// The days since 1970 of the first day of week 1 of a week-based year.
+    pub fn first_week_start(&self, year: i64) -> i64 {
+        let january_1 = days_from_civil(year, Month::January, 1);
+        let days_into_week = self.days_into_week(Weekday::from(EpochMS(january_1 * 86_400_000)));
+        let week_start = january_1 - days_into_week;
+        if 7 - days_into_week >= self.minimal_days as i64 {
+            week_start
+        } else {
+            week_start + 7
+        }
+    }
+
+    /// How many days after the start of the week a weekday is, from 0 to 6.
+    fn days_into_week(&self, weekday: Weekday) -> i64 {
+        (weekday.number_from_sunday() as i64 - self.first_day.number_from_sunday() as i64)
+            .rem_euclid(7)
+    }
+
+    /// The week that a day since 1970 falls in.
+    pub fn week_of_day(&self, days: i64) -> Week {
+        let (mut year, _, _) = civil_from_days(days);
+        if days < self.first_week_start(year) {
+            year -= 1;
+        } else if days >= self.first_week_start(year + 1) {
+            year += 1;
+        }
+        Week {
+            year,
+            week: ((days - self.first_week_start(year)) / 7 + 1) as u8,
+        }
+    }
+
+    /// The number of weeks in a week-based year, either 52 or 53.
+    pub fn weeks_in_year(&self, year: i64) -> u8 {
+        ((self.first_week_start(year + 1) - self.first_week_start(year)) / 7) as u8
+    }
+}
+
+/// A week in a week-based year. The week-based year can differ from the calendar year for
+/// the first and last few days of the year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Week {
+    pub year: i64,
+    /// Starts with 1
+    pub week: u8,
+}
+
+/// Formatted as in ISO 8601, e.g. `2021-W05`.
+impl fmt::Display for Week {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-W{:02}", self.year, self.week)
+    }
+}
+
+impl Date {
+    /// The day of the year, starting with 1 for January 1.
+    pub fn day_of_year(&self) -> u16 {
+        let days = self.epoch().days_since();
+        (days - days_from_civil(self.year, Month::January, 1) + 1) as u16
+    }
+
+    /// The ISO 8601 week-numbering year and week.
+    pub fn iso_week(&self) -> Week {
+        self.week(WeekRule::ISO)
+    }
+
+    pub fn week(&self, rule: WeekRule) -> Week {
+        rule.week_of_day(self.epoch().days_since())
+    }
+
+    /// Build a date at midnight from the year and the day of the year, e.g. the ordinal date
+    /// `2021-032` is February 1.
+    pub fn from_day_of_year(year: i64, day_of_year: u16) -> Result<Date, DateError> {
+        if day_of_year == 0 || day_of_year > Gregorian.days_in_year(year) {
+            return Err(DateError::InvalidDayOfYear(day_of_year));
+        }
+        let (month, day) = Gregorian.day_month(year, day_of_year);
+        Date::new(year, Month::try_from(month)?, day, 0, 0, 0, 0)
+    }
+
+    /// Build a date at midnight from an ISO 8601 week date, e.g. `2021-W05-1` is Monday,
+    /// February 1, 2021.
+    pub fn from_iso_week(week: Week, weekday: Weekday) -> Result<Date, DateError> {
+        Date::from_week(WeekRule::ISO, week, weekday)
+    }
+
+    pub fn from_week(rule: WeekRule, week: Week, weekday: Weekday) -> Result<Date, DateError> {
+        if week.week == 0 || week.week > rule.weeks_in_year(week.year) {
+            return Err(DateError::InvalidWeek(week));
+        }
+        let week_start = rule.first_week_start(week.year) + (week.week as i64 - 1) * 7;
+        let days = week_start + rule.days_into_week(weekday);
+        let (year, month, day) = civil_from_days(days);
+        Date::new(year, month, day, 0, 0, 0, 0)
+    }
+}
+
+#[test]
+fn test_day_of_year() {
+    let date = Date::new(2021, Month::February, 1, 12, 0, 0, 0).unwrap();
+    assert_eq!(date.day_of_year(), 32);
+    assert_eq!(
+        Date::new(2020, Month::December, 31, 0, 0, 0, 0)
+            .unwrap()
+            .day_of_year(),
+        366
+    );
+    assert_eq!(
+        Date::new(2021, Month::December, 31, 0, 0, 0, 0)
+            .unwrap()
+            .day_of_year(),
+        365
+    );
+
+    assert_eq!(
+        Date::from_day_of_year(2021, 32),
+        Date::new(2021, Month::February, 1, 0, 0, 0, 0)
+    );
+    assert_eq!(
+        Date::from_day_of_year(2020, 366),
+        Date::new(2020, Month::December, 31, 0, 0, 0, 0)
+    );
+    assert_eq!(
+        Date::from_day_of_year(2021, 366),
+        Err(DateError::InvalidDayOfYear(366))
+    );
+    assert_eq!(
+        Date::from_day_of_year(2021, 0),
+        Err(DateError::InvalidDayOfYear(0))
+    );
+}
+
+#[test]
+fn test_iso_weeks() {
+    let cases = [
+        ((2021, Month::January, 3), (2020, 53), Weekday::Sunday),
+        ((2021, Month::January, 4), (2021, 1), Weekday::Monday),
+        ((2021, Month::February, 1), (2021, 5), Weekday::Monday),
+        ((2008, Month::December, 29), (2009, 1), Weekday::Monday),
+        ((2010, Month::January, 3), (2009, 53), Weekday::Sunday),
+        ((2026, Month::December, 31), (2026, 53), Weekday::Thursday),
+        ((2027, Month::January, 1), (2026, 53), Weekday::Friday),
+    ];
+    for ((year, month, day), (week_year, week), weekday) in cases {
+        let date = Date::new(year, month, day, 0, 0, 0, 0).unwrap();
+        let expected = Week {
+            year: week_year,
+            week,
+        };
+        assert_eq!(date.iso_week(), expected, "The ISO week of {}", date);
+        assert_eq!(date.weekday, weekday);
+        assert_eq!(Date::from_iso_week(expected, weekday), Ok(date));
+    }
+
+    assert_eq!(WeekRule::ISO.weeks_in_year(2020), 53);
+    assert_eq!(WeekRule::ISO.weeks_in_year(2021), 52);
+    let week = Week {
+        year: 2021,
+        week: 53,
+    };
+    assert_eq!(
+        Date::from_iso_week(week, Weekday::Monday),
+        Err(DateError::InvalidWeek(week))
+    );
+    assert_eq!(week.to_string(), "2021-W53");
+}
+
+#[test]
+fn test_other_week_rules() {
+    // Week 1 in the US is the one with January 1, and starts on Sunday.
+    let cases = [
+        ((2020, Month::December, 27), 2021, 1),
+        ((2021, Month::January, 2), 2021, 1),
+        ((2021, Month::January, 3), 2021, 2),
+        ((2021, Month::January, 9), 2021, 2),
+        ((2021, Month::December, 25), 2021, 52),
+        ((2021, Month::December, 26), 2022, 1),
+    ];
+    for ((year, month, day), week_year, week) in cases {
+        let date = Date::new(year, month, day, 0, 0, 0, 0).unwrap();
+        let expected = Week {
+            year: week_year,
+            week,
+        };
+        assert_eq!(date.week(WeekRule::US), expected, "The US week of {}", date);
+        assert_eq!(
+            Date::from_week(WeekRule::US, expected, date.weekday),
+            Ok(date)
+        );
+    }
+
+    // Weeks starting on Saturday, where the first week needs a full 7 days.
+    let rule = WeekRule {
+        first_day: Weekday::Saturday,
+        minimal_days: 7,
+    };
+    let date = Date::new(2022, Month::January, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(date.weekday, Weekday::Saturday);
+    assert_eq!(
+        date.week(rule),
+        Week {
+            year: 2022,
+            week: 1
+        }
+    );
+    let date = Date::new(2021, Month::January, 1, 0, 0, 0, 0).unwrap();
+    assert_eq!(
+        date.week(rule),
+        Week {
+            year: 2020,
+            week: 52
+        }
+    );
+}
@@ -0,0 +1,203 @@
+//! Sources of the current time. The wall-clock time is an [EpochMS], which can jump when the
+//! system time is changed, while the monotonic time is a [Duration] since some fixed point
+//! that only ever moves forward, which is what sleeps and timeouts should be measured with.
+//!
+//! The [MockClock] only moves when it is told to, so that tests with time in them don't have
+//! to really wait.
+#![allow(dead_code)]
+
+use crate::calendar::EpochMS;
+use std::{
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub trait Clock: Send + Sync {
+    /// The wall-clock time.
+    fn now(&self) -> EpochMS;
+
+    /// The time since a fixed point, which never goes backwards.
+    fn monotonic(&self) -> Duration;
+
+    /// Block the thread until the monotonic time reaches the deadline.
+    fn sleep_until(&self, deadline: Duration) {
+        let now = self.monotonic();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.sleep_until(self.monotonic() + duration);
+    }
+}
+
+/// The monotonic time is shared by every real clock in the process, so their deadlines can
+/// be compared with each other.
+fn process_start() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+    *START.get_or_init(Instant::now)
+}
+
+/// The operating system's clock, which follows any changes to the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> EpochMS {
+        let millis = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_millis() as i64,
+            Err(error) => -(error.duration().as_millis() as i64),
+        };
+        EpochMS(millis)
+    }
+
+    fn monotonic(&self) -> Duration {
+        process_start().elapsed()
+    }
+}
+
+/// A clock that reads the system time once, and then counts forward from it with the
+/// monotonic time. Its wall-clock time never goes backwards, but it drifts from the system
+/// time when that is adjusted.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    start: EpochMS,
+    start_monotonic: Duration,
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        MonotonicClock::new()
+    }
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        MonotonicClock {
+            start: SystemClock.now(),
+            start_monotonic: SystemClock.monotonic(),
+        }
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> EpochMS {
+        self.start + (self.monotonic() - self.start_monotonic)
+    }
+
+    fn monotonic(&self) -> Duration {
+        SystemClock.monotonic()
+    }
+}
+
+#[derive(Debug)]
+struct MockTime {
+    now: EpochMS,
+    monotonic: Duration,
+}
+
+/// A clock that stands still until it is advanced. Clones share the same time, so one can
+/// be handed to the code under test while the test moves the other. Any thread sleeping on
+/// the clock wakes up once the time is advanced past its deadline.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    shared: Arc<(Mutex<MockTime>, Condvar)>,
+}
+
+impl MockClock {
+    pub fn new(now: EpochMS) -> Self {
+        MockClock {
+            shared: Arc::new((
+                Mutex::new(MockTime {
+                    now,
+                    monotonic: Duration::ZERO,
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+
+    /// Move both the wall-clock and the monotonic time forward.
+    pub fn advance(&self, duration: Duration) {
+        let (time, condvar) = &*self.shared;
+        let mut time = time.lock().expect("Failed to lock the mock time.");
+        time.now = time.now + duration;
+        time.monotonic += duration;
+        condvar.notify_all();
+    }
+
+    /// Change the wall-clock time, like the system time being adjusted. The monotonic time
+    /// stays where it is.
+    pub fn set(&self, now: EpochMS) {
+        let (time, _) = &*self.shared;
+        time.lock().expect("Failed to lock the mock time.").now = now;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> EpochMS {
+        let (time, _) = &*self.shared;
+        time.lock().expect("Failed to lock the mock time.").now
+    }
+
+    fn monotonic(&self) -> Duration {
+        let (time, _) = &*self.shared;
+        time.lock()
+            .expect("Failed to lock the mock time.")
+            .monotonic
+    }
+
+    /// Wait for another thread to advance the clock to the deadline.
+    fn sleep_until(&self, deadline: Duration) {
+        let (time, condvar) = &*self.shared;
+        let time = time.lock().expect("Failed to lock the mock time.");
+        let _time = condvar
+            .wait_while(time, |time| time.monotonic < deadline)
+            .expect("Failed to lock the mock time.");
+    }
+}
+
+#[test]
+fn test_system_clocks() {
+    // Some time after 2020.
+    assert!(SystemClock.now() > EpochMS(1_577_836_800_000));
+
+    let clock = MonotonicClock::new();
+    let before = (clock.now(), clock.monotonic());
+    clock.sleep(Duration::from_millis(5));
+    let after = (clock.now(), clock.monotonic());
+    assert!(after.0 >= before.0 + Duration::from_millis(5));
+    assert!(after.1 >= before.1 + Duration::from_millis(5));
+}
+
+#[test]
+fn test_mock_clock() {
+    let clock = MockClock::new(EpochMS(1_000_000));
+    assert_eq!(clock.now(), EpochMS(1_000_000));
+    assert_eq!(clock.monotonic(), Duration::ZERO);
+
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(clock.now(), EpochMS(1_002_000));
+    assert_eq!(clock.monotonic(), Duration::from_secs(2));
+
+    // Setting the time back doesn't move the monotonic time.
+    clock.set(EpochMS(0));
+    assert_eq!(clock.now(), EpochMS(0));
+    assert_eq!(clock.monotonic(), Duration::from_secs(2));
+
+    // A sleeping thread waits until the clock is advanced far enough.
+    let sleeper = {
+        let clock = clock.clone();
+        thread::spawn(move || {
+            clock.sleep_until(Duration::from_secs(62));
+            clock.monotonic()
+        })
+    };
+    while clock.monotonic() < Duration::from_secs(62) {
+        clock.advance(Duration::from_secs(10));
+        thread::yield_now();
+    }
+    assert_eq!(sleeper.join().unwrap(), Duration::from_secs(62));
+}
@@ -1,36 +1,63 @@
 #![cfg(test)]
+
+mod channel;
+mod local;
+mod sync;
+mod timer;
+
+use crate::clock::MockClock;
 use futures::{
-    future::{BoxFuture, FutureExt},
+    future::{self, BoxFuture, Either, FutureExt},
     task::{waker_ref, ArcWake},
 };
 use std::{
+    any::Any,
+    cell::Cell,
+    collections::VecDeque,
+    fmt,
     future::Future,
-    pin::Pin,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    ptr,
     sync::{
-        mpsc::{sync_channel, Receiver, SyncSender},
-        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Barrier, Condvar, Mutex, MutexGuard, Weak,
     },
     task::{Context, Poll, Waker},
     thread,
-    time::{Duration, Instant},
+    time::Duration,
 };
+use timer::{TimerDriver, TimerHandle};
 
-/// Shared state between the future and the waiting thread.
-struct AsyncState {
+/// Shared state between the future and the waiting thread, which hands over a single value.
+struct AsyncState<T = Duration> {
     waker: Option<Waker>,
-    result: Option<Duration>,
+    result: Option<T>,
+    /// Set when one side went away before the value was handed over, so that the other side
+    /// doesn't wait for it.
+    closed: bool,
 }
 
-impl AsyncState {
+impl<T> AsyncState<T> {
     pub fn new() -> Self {
         AsyncState {
             waker: None,
             result: None,
+            closed: false,
+        }
+    }
+
+    pub fn complete(&mut self, result: T) {
+        self.result = Some(result);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
         }
     }
 
-    pub fn complete(&mut self, duration: Duration) {
-        self.result = Some(duration);
+    pub fn close(&mut self) {
+        self.closed = true;
 
         if let Some(waker) = self.waker.take() {
             waker.wake();
@@ -38,6 +65,82 @@ impl AsyncState {
     }
 }
 
+/// The wakers of the futures waiting on the same thing, in the order that they started
+/// waiting. Each future keeps the id of its entry, so that polling it again replaces its
+/// waker rather than adding another one, and dropping it can take its waker back out. Like
+/// [AsyncState::complete], the wakers are woken while the lock around the list is held, as
+/// waking only queues the task.
+#[derive(Default)]
+struct WaitList {
+    wakers: VecDeque<(u64, Waker)>,
+    next_id: u64,
+}
+
+impl WaitList {
+    /// Add or update the future's waker. A future that was woken and is still waiting goes
+    /// to the back of the list.
+    fn register(&mut self, id: &mut Option<u64>, waker: &Waker) {
+        let registered = id.and_then(|id| self.wakers.iter_mut().find(|(other, _)| *other == id));
+        match registered {
+            Some((_, registered)) => {
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+            }
+            None => {
+                let new_id = id.unwrap_or_else(|| {
+                    self.next_id += 1;
+                    self.next_id
+                });
+                self.wakers.push_back((new_id, waker.clone()));
+                *id = Some(new_id);
+            }
+        }
+    }
+
+    /// Take the future's waker out, and return whether it was still waiting. It wasn't when
+    /// it had already been woken.
+    fn remove(&mut self, id: u64) -> bool {
+        match self.wakers.iter().position(|(other, _)| *other == id) {
+            Some(index) => {
+                self.wakers.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the future is still waiting, rather than having been woken.
+    fn contains(&self, id: u64) -> bool {
+        self.wakers.iter().any(|(other, _)| *other == id)
+    }
+
+    fn len(&self) -> usize {
+        self.wakers.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.wakers.is_empty()
+    }
+
+    /// Wake the future that has waited the longest, and return whether there was one.
+    fn wake_one(&mut self) -> bool {
+        match self.wakers.pop_front() {
+            Some((_, waker)) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn wake_all(&mut self) {
+        for (_, waker) in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
 pub struct AsyncThreadSleeper {
     async_state: Arc<Mutex<AsyncState>>,
 }
@@ -57,84 +160,726 @@ impl Future for AsyncThreadSleeper {
     }
 }
 
+impl Drop for AsyncThreadSleeper {
+    fn drop(&mut self) {
+        if let Ok(mut async_state) = self.async_state.lock() {
+            async_state.waker = None;
+        }
+    }
+}
+
 impl AsyncThreadSleeper {
     pub fn new(duration: Duration) -> Self {
-        // The shared state is what is needed to coordinate the future.
-        let async_state = Arc::new(Mutex::new(AsyncState::new()));
-        let async_state2 = async_state.clone();
+        AsyncThreadSleeper::with_timer(duration, &TimerHandle::global())
+    }
 
-        thread::spawn(move || {
-            let start = Instant::now();
+    /// Sleep on a timer driver, which lets tests use one with a [MockClock] rather than
+    /// really waiting. Despite the name, every sleeper on a driver shares its one thread.
+    pub fn with_timer(duration: Duration, timer: &TimerHandle) -> Self {
+        let deadline = timer.clock().monotonic() + duration;
+        AsyncThreadSleeper {
+            async_state: timer.register(deadline),
+        }
+    }
+}
 
-            // Sleep for the duration specified. This makes the behavior async.
-            thread::sleep(duration);
+/// How many live tasks [Spawner::spawn_when_ready] allows by default. The queues themselves
+/// are unbounded, so [Spawner::spawn] and waking a task never wait.
+const DEFAULT_CAPACITY: usize = 10_000;
 
-            // Mark the async state as completed.
-            async_state2
-                .lock()
-                .expect("Failed to lock shared state mutex")
-                .complete(start.elapsed());
-        });
+/// How many tasks a worker runs between checks of the injector.
+const INJECTOR_INTERVAL: usize = 61;
 
-        AsyncThreadSleeper {
-            async_state: async_state,
+thread_local! {
+    /// The executor and the index of the worker that the thread is running, so that tasks
+    /// woken from a worker go onto its own queue.
+    static CURRENT_WORKER: Cell<Option<(*const Shared, usize)>> = const { Cell::new(None) };
+}
+
+/// The counts that decide when workers should sleep, and when they are done.
+struct State {
+    queued: usize,
+    spawners: usize,
+    /// The spawned tasks that haven't been dropped yet, along with capacity reserved by
+    /// [Spawner::spawn_when_ready] for tasks that are about to be spawned.
+    live_tasks: usize,
+    capacity: usize,
+    /// The spawners waiting for the live tasks to go under the capacity.
+    capacity_wakers: Vec<Waker>,
+    /// Set when [Executor::block_on] has its output, so that the workers return even though
+    /// there could still be tasks.
+    stopped: bool,
+}
+
+impl State {
+    fn is_done(&self) -> bool {
+        self.spawners == 0 && self.live_tasks == 0
+    }
+}
+
+/// The queues and counts shared between the [Executor], its [Spawner]s and the [Task]s.
+struct Shared {
+    /// Tasks that were spawned or woken from outside of the workers.
+    injector: Mutex<VecDeque<Arc<Task>>>,
+    /// Each worker takes from the front of its own queue, and steals from the back of the
+    /// others when both it and the injector are empty.
+    local_queues: Vec<Mutex<VecDeque<Arc<Task>>>>,
+    state: Mutex<State>,
+    /// Signalled when there is work, or when the last task is done.
+    work: Condvar,
+}
+
+impl Shared {
+    fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("Failed to lock the executor state.")
+    }
+
+    /// Queue the task, unless it is already in a queue. The flag is cleared right before
+    /// the task is polled, so a wake during the poll queues it again and isn't lost.
+    fn schedule(self: &Arc<Self>, task: Arc<Task>) {
+        if task.scheduled.swap(true, Ordering::AcqRel) {
+            return;
         }
+        self.lock_state().queued += 1;
+
+        let worker = CURRENT_WORKER.with(|worker| worker.get());
+        let queue = match worker {
+            Some((shared, index)) if ptr::eq(shared, Arc::as_ptr(self)) => {
+                &self.local_queues[index]
+            }
+            _ => &self.injector,
+        };
+        queue.lock().unwrap().push_back(task);
+        self.work.notify_one();
+    }
+
+    /// Every so often the injector is checked first, so that tasks that keep waking
+    /// themselves on a worker's own queue can't starve the ones spawned from outside.
+    fn find_task(&self, index: usize, tick: usize) -> Option<Arc<Task>> {
+        let injected = if tick.is_multiple_of(INJECTOR_INTERVAL) {
+            self.injector.lock().unwrap().pop_front()
+        } else {
+            None
+        };
+        // Only hold one queue's lock at a time, or two workers stealing from each other
+        // could deadlock.
+        let task = injected
+            .or_else(|| self.local_queues[index].lock().unwrap().pop_front())
+            .or_else(|| self.injector.lock().unwrap().pop_front())
+            .or_else(|| {
+                let count = self.local_queues.len();
+                (1..count)
+                    .map(|offset| &self.local_queues[(index + offset) % count])
+                    .find_map(|queue| queue.lock().unwrap().pop_back())
+            })?;
+        self.lock_state().queued -= 1;
+        Some(task)
+    }
+
+    fn run_worker(&self, index: usize) {
+        CURRENT_WORKER.with(|worker| worker.set(Some((self as *const Shared, index))));
+        for tick in 1.. {
+            if let Some(task) = self.find_task(index, tick) {
+                task.poll();
+                continue;
+            }
+            let state = self.lock_state();
+            let state = self
+                .work
+                .wait_while(state, |state| {
+                    state.queued == 0 && !state.is_done() && !state.stopped
+                })
+                .expect("Failed to lock the executor state.");
+            if state.stopped || (state.queued == 0 && state.is_done()) {
+                break;
+            }
+        }
+        CURRENT_WORKER.with(|worker| worker.set(None));
     }
 }
 
+/// Runs the spawned tasks on a number of worker threads, until every [Spawner] is dropped
+/// and there are no tasks left that could still be woken.
 struct Executor {
-    ready_queue: Receiver<Arc<Task>>,
+    shared: Arc<Shared>,
 }
 
 impl Executor {
+    /// One worker for each core.
     fn new() -> (Self, Spawner) {
-        let (task_sender, ready_queue) = sync_channel(10_000 /* max tasks */);
-        (Executor { ready_queue }, Spawner { task_sender })
+        let workers = thread::available_parallelism().map_or(1, |count| count.get());
+        Executor::with_workers(workers)
+    }
+
+    fn with_workers(workers: usize) -> (Self, Spawner) {
+        Executor::with_capacity(workers, DEFAULT_CAPACITY)
+    }
+
+    /// The capacity only limits [Spawner::spawn_when_ready].
+    fn with_capacity(workers: usize, capacity: usize) -> (Self, Spawner) {
+        assert!(workers > 0, "The executor needs at least one worker.");
+        assert!(
+            capacity > 0,
+            "The executor needs room for at least one task."
+        );
+        let shared = Arc::new(Shared {
+            injector: Mutex::new(VecDeque::new()),
+            local_queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            state: Mutex::new(State {
+                queued: 0,
+                spawners: 1,
+                live_tasks: 0,
+                capacity,
+                capacity_wakers: Vec::new(),
+                stopped: false,
+            }),
+            work: Condvar::new(),
+        });
+        let spawner = Spawner {
+            shared: shared.clone(),
+        };
+        (Executor { shared }, spawner)
     }
 
+    /// Block until all of the tasks are done.
     fn run(&self) {
-        while let Ok(task) = self.ready_queue.recv() {
-            let mut future_slot = task.future.lock().unwrap();
-            if let Some(mut future) = future_slot.take() {
-                let waker = waker_ref(&task);
-                let context = &mut Context::from_waker(&*waker);
-                if future.as_mut().poll(context).is_pending() {
-                    *future_slot = Some(future);
-                }
+        self.shared.lock_state().stopped = false;
+        thread::scope(|scope| {
+            for index in 0..self.shared.local_queues.len() {
+                let shared = &self.shared;
+                scope.spawn(move || shared.run_worker(index));
+            }
+        });
+    }
+
+    /// Run the workers until the future completes, and return its output. Unlike
+    /// [Executor::run], this doesn't wait for the spawners to be dropped, and any tasks that
+    /// are left stay queued for the next time the executor runs.
+    fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
+        self.shared.lock_state().stopped = false;
+        thread::scope(|scope| {
+            for index in 0..self.shared.local_queues.len() {
+                let shared = &self.shared;
+                scope.spawn(move || shared.run_worker(index));
+            }
+            // The workers are stopped even when the future panics, or the scope would never
+            // finish joining them.
+            let _stop = StopWorkers(&self.shared);
+            block_on(future)
+        })
+    }
+
+    /// Poll the queued tasks on the current thread until none are left, and then return
+    /// without waiting for timers or other threads to wake anything. The executor shouldn't
+    /// be running on its workers at the same time.
+    fn run_until_stalled(&self) {
+        let shared: &Shared = &self.shared;
+        CURRENT_WORKER.with(|worker| worker.set(Some((shared as *const Shared, 0))));
+        for tick in 1.. {
+            match shared.find_task(0, tick) {
+                Some(task) => task.poll(),
+                None => break,
             }
         }
+        CURRENT_WORKER.with(|worker| worker.set(None));
     }
 }
 
-/// Takes a future, and sends it to the [Executor] via the `task_sender`.
-#[derive(Clone)]
+/// Tells the workers to return, when it is dropped.
+struct StopWorkers<'a>(&'a Shared);
+
+impl Drop for StopWorkers<'_> {
+    fn drop(&mut self) {
+        self.0.lock_state().stopped = true;
+        self.0.work.notify_all();
+    }
+}
+
+/// Wakes a thread that is parked in [block_on].
+struct ThreadWaker {
+    thread: thread::Thread,
+    woken: AtomicBool,
+}
+
+impl ArcWake for ThreadWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.store(true, Ordering::Release);
+        arc_self.thread.unpark();
+    }
+}
+
+/// Poll the future on the current thread, and park the thread whenever it is pending, until
+/// it completes. Nothing else runs in the meantime, so it shouldn't be called from inside of
+/// a task, and the future shouldn't wait on tasks unless an executor is running elsewhere.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let thread_waker = Arc::new(ThreadWaker {
+        thread: thread::current(),
+        woken: AtomicBool::new(false),
+    });
+    let waker = waker_ref(&thread_waker);
+    let context = &mut Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(context) {
+            return output;
+        }
+        // The flag guards against the thread being unparked for some other reason.
+        while !thread_waker.woken.swap(false, Ordering::AcqRel) {
+            thread::park();
+        }
+    }
+}
+
+/// Takes a future, and queues it on the [Executor]. Futures spawned from inside of a task
+/// go onto the queue of the worker that is running it.
 struct Spawner {
-    task_sender: SyncSender<Arc<Task>>,
+    shared: Arc<Shared>,
+}
+
+impl Clone for Spawner {
+    fn clone(&self) -> Self {
+        self.shared.lock_state().spawners += 1;
+        Spawner {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Spawner {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock_state();
+        state.spawners -= 1;
+        if state.is_done() {
+            self.shared.work.notify_all();
+        }
+    }
 }
 
 impl Spawner {
-    fn spawn(&self, future: impl Future<Output = ()> + 'static + Send) {
-        let future = future.boxed();
+    /// Queue the future on the executor. Its output, or its panic, goes to the returned
+    /// [JoinHandle], and the task keeps running if the handle is dropped.
+    fn spawn<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> JoinHandle<T> {
+        self.shared.lock_state().live_tasks += 1;
+        self.spawn_reserved(future)
+    }
+
+    /// Wait until there are fewer live tasks than the executor's capacity, and then spawn
+    /// the future. This lets a producer slow down rather than queue without end.
+    async fn spawn_when_ready<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> JoinHandle<T> {
+        future::poll_fn(|cx| {
+            let mut state = self.shared.lock_state();
+            if state.live_tasks < state.capacity {
+                state.live_tasks += 1;
+                return Poll::Ready(());
+            }
+            if !state
+                .capacity_wakers
+                .iter()
+                .any(|waker| waker.will_wake(cx.waker()))
+            {
+                state.capacity_wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await;
+        self.spawn_reserved(future)
+    }
+
+    /// Spawn a task that was already counted in the live tasks.
+    fn spawn_reserved<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> JoinHandle<T> {
+        let (future, join_state) = joinable(future);
         let task = Arc::new(Task {
-            future: Mutex::new(Some(future)),
-            task_sender: self.task_sender.clone(),
+            future: Mutex::new(Some(future.boxed())),
+            scheduled: AtomicBool::new(false),
+            aborted: AtomicBool::new(false),
+            shared: self.shared.clone(),
         });
-        self.task_sender.send(task).expect("too many tasks queued");
+        let abort_handle = AbortHandle {
+            task: Arc::downgrade(&task) as Weak<dyn Abort>,
+        };
+        self.shared.schedule(task);
+        JoinHandle {
+            join_state,
+            abort_handle,
+        }
     }
 }
 
+/// Wrap a future so that its output, or its panic, goes to the state of a [JoinHandle].
+fn joinable<F: Future>(future: F) -> (impl Future<Output = ()>, Arc<Mutex<JoinState<F::Output>>>) {
+    let join_state = Arc::new(Mutex::new(JoinState {
+        result: None,
+        waker: None,
+        detached: false,
+    }));
+    let completer = JoinCompleter {
+        join_state: Some(join_state.clone()),
+    };
+    let future = async move {
+        // The future isn't used again after a panic, so it can't be seen half-updated.
+        let result = AssertUnwindSafe(future)
+            .catch_unwind()
+            .await
+            .map_err(JoinError::Panicked);
+        completer.complete(result);
+    };
+    (future, join_state)
+}
+
+/// Why a task didn't produce its output.
+#[derive(Debug)]
+enum JoinError {
+    /// The task panicked, with the value that it panicked with.
+    Panicked(Box<dyn Any + Send + 'static>),
+    /// The task was aborted, or dropped without ever being woken again.
+    Cancelled,
+}
+
+impl JoinError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, JoinError::Cancelled)
+    }
+
+    /// Continue the panic on the current thread. A cancelled task panics with a new message.
+    fn resume_panic(self) -> ! {
+        match self {
+            JoinError::Panicked(payload) => panic::resume_unwind(payload),
+            JoinError::Cancelled => panic!("task was cancelled"),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Panicked(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("Box<dyn Any>");
+                write!(f, "task panicked: {}", message)
+            }
+            JoinError::Cancelled => write!(f, "task was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// Shared between a task and its [JoinHandle].
+struct JoinState<T> {
+    result: Option<Result<T, JoinError>>,
+    waker: Option<Waker>,
+    detached: bool,
+}
+
+impl<T> JoinState<T> {
+    fn complete(&mut self, result: Result<T, JoinError>) {
+        if self.detached {
+            return;
+        }
+        self.result = Some(result);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Owned by the spawned future, so that the [JoinHandle] hears about the task even when its
+/// future is dropped before it finishes.
+struct JoinCompleter<T> {
+    join_state: Option<Arc<Mutex<JoinState<T>>>>,
+}
+
+impl<T> JoinCompleter<T> {
+    fn complete(mut self, result: Result<T, JoinError>) {
+        if let Some(join_state) = self.join_state.take() {
+            join_state
+                .lock()
+                .expect("Failed to lock the join state.")
+                .complete(result);
+        }
+    }
+}
+
+impl<T> Drop for JoinCompleter<T> {
+    fn drop(&mut self) {
+        if let Some(join_state) = self.join_state.take() {
+            if let Ok(mut join_state) = join_state.lock() {
+                join_state.complete(Err(JoinError::Cancelled));
+            }
+        }
+    }
+}
+
+/// Resolves to the output of a spawned task, or the error when it panicked or was aborted.
+struct JoinHandle<T> {
+    join_state: Arc<Mutex<JoinState<T>>>,
+    abort_handle: AbortHandle,
+}
+
+impl<T> JoinHandle<T> {
+    fn lock(&self) -> MutexGuard<'_, JoinState<T>> {
+        self.join_state
+            .lock()
+            .expect("Failed to lock the join state.")
+    }
+
+    /// Whether the task has finished, either with its output or with a panic.
+    fn is_finished(&self) -> bool {
+        self.lock().result.is_some()
+    }
+
+    /// Stop the task, which resolves the handle with [JoinError::Cancelled] unless the task
+    /// already finished.
+    fn abort(&self) {
+        self.abort_handle.abort();
+    }
+
+    /// A handle that can abort the task without waiting on its output.
+    fn abort_handle(&self) -> AbortHandle {
+        self.abort_handle.clone()
+    }
+
+    /// Let the task run on its own. Its output is dropped as soon as it is ready. This is
+    /// the same as dropping the handle, but says so at the call site.
+    fn detach(self) {}
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        let mut join_state = self.lock();
+        join_state.detached = true;
+        join_state.result = None;
+        join_state.waker = None;
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    /// Panics if it is polled again after it has resolved.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut join_state = self.lock();
+        if let Some(result) = join_state.result.take() {
+            join_state.detached = true;
+            return Poll::Ready(result);
+        }
+        assert!(!join_state.detached, "JoinHandle polled after completion");
+        join_state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// A spawned future. It stays live for as long as anything, such as a waker, holds on to it.
 struct Task {
     future: Mutex<Option<BoxFuture<'static, ()>>>,
-    task_sender: SyncSender<Arc<Task>>,
+    /// Whether the task is in a queue, so that waking it many times only queues it once.
+    scheduled: AtomicBool,
+    aborted: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Task {
+    fn poll(self: &Arc<Self>) {
+        let mut future_slot = self.future.lock().unwrap();
+        self.scheduled.store(false, Ordering::Release);
+        if self.aborted.load(Ordering::Acquire) {
+            // Drop the future here rather than in the thread that aborted it, which could be
+            // inside of the future's own poll.
+            future_slot.take();
+            return;
+        }
+        if let Some(mut future) = future_slot.take() {
+            let waker = waker_ref(self);
+            let context = &mut Context::from_waker(&waker);
+            if future.as_mut().poll(context).is_pending() {
+                *future_slot = Some(future);
+            }
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        let capacity_wakers = {
+            let mut state = self.shared.lock_state();
+            state.live_tasks -= 1;
+            if state.is_done() {
+                self.shared.work.notify_all();
+            }
+            mem::take(&mut state.capacity_wakers)
+        };
+        // Every waiting spawner gets another look, as one that was woken could have been
+        // dropped without taking the room.
+        for waker in capacity_wakers {
+            waker.wake();
+        }
+    }
 }
 
 impl ArcWake for Task {
+    /// Waking an aborted task does nothing, as it was already queued to drop its future.
     fn wake_by_ref(arc_self: &Arc<Self>) {
-        let cloned = arc_self.clone();
-        arc_self
-            .task_sender
-            .send(cloned)
-            .expect("too many tasks queued");
+        if !arc_self.aborted.load(Ordering::Acquire) {
+            arc_self.shared.schedule(arc_self.clone());
+        }
+    }
+}
+
+/// A task on any of the executors, which can be told to drop its future.
+trait Abort: Send + Sync {
+    fn abort(self: Arc<Self>);
+}
+
+impl Abort for Task {
+    /// Queue the task one last time, so that it drops its future at its next poll. A task
+    /// that is already queued isn't queued again.
+    fn abort(self: Arc<Self>) {
+        if !self.aborted.swap(true, Ordering::AcqRel) {
+            self.shared.schedule(self.clone());
+        }
+    }
+}
+
+/// Aborts a task, without keeping it alive.
+#[derive(Clone)]
+struct AbortHandle {
+    task: Weak<dyn Abort>,
+}
+
+impl AbortHandle {
+    fn abort(&self) {
+        if let Some(task) = self.task.upgrade() {
+            task.abort();
+        }
+    }
+}
+
+/// Shared between a [CancellationToken], its clones, and the futures waiting on it.
+struct TokenState {
+    cancelled: bool,
+    /// The wakers of the [Cancelled] futures.
+    wakers: WaitList,
+    children: Vec<Weak<Mutex<TokenState>>>,
+}
+
+/// Signals tasks to stop what they are doing. Cancelling a token cancels all of its children
+/// as well, but cancelling a child leaves its parent alone.
+#[derive(Clone)]
+struct CancellationToken {
+    state: Arc<Mutex<TokenState>>,
+}
+
+impl CancellationToken {
+    fn new() -> Self {
+        CancellationToken {
+            state: Arc::new(Mutex::new(TokenState {
+                cancelled: false,
+                wakers: WaitList::default(),
+                children: Vec::new(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TokenState> {
+        self.state.lock().expect("Failed to lock the token state.")
+    }
+
+    /// A token that is cancelled along with this one. It starts out cancelled when this one
+    /// already is.
+    fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut state = self.lock();
+        if state.cancelled {
+            child.lock().cancelled = true;
+        } else {
+            state.children.retain(|child| child.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.state));
+        }
+        child
+    }
+
+    fn cancel(&self) {
+        let children = {
+            let mut state = self.lock();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            state.wakers.wake_all();
+            mem::take(&mut state.children)
+        };
+        // The children are cancelled once this lock is released, so that only one token is
+        // ever locked at a time.
+        for child in children.iter().filter_map(Weak::upgrade) {
+            CancellationToken { state: child }.cancel();
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.lock().cancelled
+    }
+
+    /// A future that resolves once the token is cancelled.
+    fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+            id: None,
+        }
+    }
+
+    /// Run the future until it finishes, or until the token is cancelled, in which case the
+    /// future is dropped and there is no output.
+    async fn run_until_cancelled<F: Future>(&self, future: F) -> Option<F::Output> {
+        match future::select(self.cancelled(), pin!(future)).await {
+            Either::Left(_) => None,
+            Either::Right((output, _)) => Some(output),
+        }
+    }
+}
+
+/// Resolves once its [CancellationToken] is cancelled.
+struct Cancelled {
+    token: CancellationToken,
+    /// The id of its waker in the token, once it has been polled.
+    id: Option<u64>,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let token = self.token.clone();
+        let mut state = token.lock();
+        if state.cancelled {
+            return Poll::Ready(());
+        }
+        state.wakers.register(&mut self.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+/// Remove the waker, so that a token that is never cancelled doesn't keep the task alive.
+impl Drop for Cancelled {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            if let Ok(mut state) = self.token.state.lock() {
+                state.wakers.remove(id);
+            }
+        }
     }
 }
 
@@ -159,3 +904,429 @@ fn test_futures() {
 
     executor.run();
 }
+
+#[test]
+fn test_futures_mock_clock() {
+    use crate::calendar::EpochMS;
+    let (executor, spawner) = Executor::new();
+    let clock = MockClock::new(EpochMS(0));
+    let driver = TimerDriver::new(clock.clone());
+    let durations = Arc::new(Mutex::new(Vec::new()));
+
+    for i in 1..=10 {
+        let sleeper = AsyncThreadSleeper::with_timer(Duration::from_secs(i), &driver.handle());
+        let durations = durations.clone();
+        spawner.spawn(async move {
+            let duration = sleeper.await;
+            durations.lock().unwrap().push(duration);
+        });
+    }
+    drop(spawner);
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(driver.turn(), None);
+    executor.run();
+
+    // Every sleeper wakes at once, at the time the clock was advanced to.
+    let durations = durations.lock().unwrap();
+    assert_eq!(*durations, vec![Duration::from_secs(10); 10]);
+}
+
+#[test]
+fn test_executor_workers() {
+    // Every task waits for all of the others, so they only finish when each one runs on its
+    // own worker at the same time.
+    let (executor, spawner) = Executor::with_workers(4);
+    let barrier = Arc::new(Barrier::new(4));
+    let finished = Arc::new(Mutex::new(Vec::new()));
+    for i in 0..4 {
+        let barrier = barrier.clone();
+        let finished = finished.clone();
+        spawner.spawn(async move {
+            barrier.wait();
+            finished.lock().unwrap().push((i, thread::current().id()));
+        });
+    }
+    drop(spawner);
+    executor.run();
+
+    let mut finished = finished.lock().unwrap();
+    finished.sort_by_key(|(i, _)| *i);
+    let mut threads: Vec<_> = finished.iter().map(|(_, thread)| *thread).collect();
+    threads.dedup();
+    assert_eq!(finished.len(), 4);
+    assert_eq!(threads.len(), 4);
+}
+
+#[test]
+fn test_executor_work_stealing() {
+    // Tasks spawned from inside of a task go onto that worker's queue, so the two tasks
+    // waiting on each other only finish if another worker steals one of them.
+    let (executor, spawner) = Executor::with_workers(2);
+    let barrier = Arc::new(Barrier::new(2));
+    let count = Arc::new(Mutex::new(0));
+    let inner_spawner = spawner.clone();
+    {
+        let count = count.clone();
+        spawner.spawn(async move {
+            for _ in 0..2 {
+                let barrier = barrier.clone();
+                let count = count.clone();
+                inner_spawner.spawn(async move {
+                    barrier.wait();
+                    *count.lock().unwrap() += 1;
+                });
+            }
+        });
+    }
+    drop(spawner);
+    executor.run();
+    assert_eq!(*count.lock().unwrap(), 2);
+}
+
+/// Let the other tasks run, by waking the task and returning to the executor once.
+async fn yield_now() {
+    let mut yielded = false;
+    futures::future::poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+#[test]
+fn test_executor_many_tasks() {
+    // More tasks than the old queue could hold, each woken a few times, including from the
+    // workers while the queues are full.
+    let (executor, spawner) = Executor::with_workers(3);
+    let total = Arc::new(Mutex::new(0));
+    let feeder = {
+        let spawner = spawner.clone();
+        let total = total.clone();
+        thread::spawn(move || {
+            for i in 0..30_000 {
+                let total = total.clone();
+                spawner.spawn(async move {
+                    for _ in 0..3 {
+                        yield_now().await;
+                    }
+                    *total.lock().unwrap() += i;
+                });
+            }
+        })
+    };
+    drop(spawner);
+    executor.run();
+    feeder.join().unwrap();
+    let count = 30_000;
+    assert_eq!(*total.lock().unwrap(), count * (count - 1) / 2);
+}
+
+#[test]
+fn test_wake_deduplication() {
+    // Wakes itself many times on each poll, but is only queued once for all of them.
+    let polls = Arc::new(Mutex::new(0));
+    let (executor, spawner) = Executor::with_workers(1);
+    {
+        let polls = polls.clone();
+        spawner.spawn(future::poll_fn(move |cx| {
+            let mut polls = polls.lock().unwrap();
+            *polls += 1;
+            if *polls == 3 {
+                return Poll::Ready(());
+            }
+            for _ in 0..1_000 {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }));
+    }
+    drop(spawner);
+    executor.run();
+    assert_eq!(*polls.lock().unwrap(), 3);
+}
+
+#[test]
+fn test_spawn_when_ready() {
+    let (executor, spawner) = Executor::with_capacity(2, 2);
+    let token = CancellationToken::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    // Fill the capacity with a task that waits on the token, and the producer itself.
+    {
+        let token = token.clone();
+        let order = order.clone();
+        spawner.spawn(async move {
+            token.cancelled().await;
+            order.lock().unwrap().push("blocker finished");
+        });
+    }
+    let producer = spawner.clone();
+    let produced = spawner.spawn(async move {
+        let waiting = producer.spawn_when_ready(async {});
+        let mut waiting = pin!(waiting);
+        // There is no room, so spawning waits until the blocker finishes.
+        assert!(futures::poll!(waiting.as_mut()).is_pending());
+        order.lock().unwrap().push("waiting");
+        token.cancel();
+        waiting.await.await.unwrap();
+        order.lock().unwrap().push("spawned");
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["waiting", "blocker finished", "spawned"]
+        );
+    });
+    drop(spawner);
+    executor.run();
+    block_on(produced).unwrap();
+}
+
+#[test]
+fn test_block_on() {
+    assert_eq!(block_on(async { 6 * 7 }), 42);
+    let duration = block_on(AsyncThreadSleeper::new(Duration::from_millis(5)));
+    assert!(duration >= Duration::from_millis(5));
+
+    // The spawner stays alive across both calls, which would keep `run` from returning.
+    let (executor, spawner) = Executor::with_workers(2);
+    let sum = executor.block_on(async {
+        let handles: Vec<_> = (1..=10)
+            .map(|i| {
+                spawner.spawn(async move {
+                    yield_now().await;
+                    i
+                })
+            })
+            .collect();
+        let mut sum = 0;
+        for handle in handles {
+            sum += handle.await.unwrap();
+        }
+        sum
+    });
+    assert_eq!(sum, 55);
+
+    // A task that is still waiting is left for the next run.
+    let token = CancellationToken::new();
+    let waiting = {
+        let token = token.clone();
+        spawner.spawn(async move { token.cancelled().await })
+    };
+    executor.block_on(yield_now());
+    assert!(!waiting.is_finished());
+    token.cancel();
+    drop(spawner);
+    executor.run();
+    assert!(waiting.is_finished());
+}
+
+#[test]
+fn test_run_until_stalled() {
+    use crate::calendar::EpochMS;
+    let (executor, spawner) = Executor::with_workers(2);
+    let clock = MockClock::new(EpochMS(0));
+    let driver = TimerDriver::new(clock.clone());
+    let count = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..10 {
+        let count = count.clone();
+        spawner.spawn(async move {
+            for _ in 0..3 {
+                yield_now().await;
+            }
+            count.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    let sleeper = AsyncThreadSleeper::with_timer(Duration::from_secs(1), &driver.handle());
+    let sleeping = spawner.spawn(sleeper);
+
+    // The tasks that were ready have all run, but the sleeper is still waiting on its timer.
+    executor.run_until_stalled();
+    assert_eq!(count.load(Ordering::SeqCst), 10);
+    assert!(!sleeping.is_finished());
+
+    clock.advance(Duration::from_secs(1));
+    driver.turn();
+    executor.run_until_stalled();
+    assert!(sleeping.is_finished());
+}
+
+#[test]
+fn test_join_handle() {
+    let (executor, spawner) = Executor::with_workers(2);
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let squares: Vec<_> = (1..=5)
+        .map(|i| {
+            spawner.spawn(async move {
+                yield_now().await;
+                i * i
+            })
+        })
+        .collect();
+    let panicked = spawner.spawn(async {
+        yield_now().await;
+        panic!("the task failed");
+    });
+    let text = spawner.spawn(async { String::from("text") });
+
+    let checked = {
+        let results = results.clone();
+        spawner.spawn(async move {
+            for handle in squares {
+                let square = handle.await.unwrap();
+                results.lock().unwrap().push(square);
+            }
+            let error = panicked.await.unwrap_err();
+            assert_eq!(error.to_string(), "task panicked: the task failed");
+            assert_eq!(text.await.unwrap(), "text");
+        })
+    };
+    drop(spawner);
+
+    // The panic is reported through the handle, so the executor keeps going.
+    executor.run();
+    block_on(checked).unwrap();
+    assert_eq!(*results.lock().unwrap(), [1, 4, 9, 16, 25]);
+}
+
+#[test]
+fn test_join_handle_detach() {
+    let (executor, spawner) = Executor::with_workers(1);
+    let ran = Arc::new(Mutex::new(false));
+    {
+        let ran = ran.clone();
+        spawner
+            .spawn(async move {
+                yield_now().await;
+                *ran.lock().unwrap() = true;
+            })
+            .detach();
+    }
+    let finished = spawner.spawn(async { 1 });
+    drop(spawner);
+    executor.run();
+
+    assert!(*ran.lock().unwrap(), "A detached task still runs.");
+    assert!(finished.is_finished());
+
+    let error = JoinError::Panicked(Box::new(String::from("owned message")));
+    assert_eq!(error.to_string(), "task panicked: owned message");
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| error.resume_panic())).unwrap_err();
+    assert_eq!(payload.downcast_ref::<String>().unwrap(), "owned message");
+}
+
+#[test]
+fn test_abort() {
+    /// Records when the future that owns it is dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let (executor, spawner) = Executor::with_workers(2);
+    let clock = MockClock::new(crate::calendar::EpochMS(0));
+    let driver = TimerDriver::new(clock.clone());
+    let dropped = Arc::new(AtomicBool::new(false));
+
+    // A task that would wake itself forever.
+    let spinning = spawner.spawn(async {
+        loop {
+            yield_now().await;
+        }
+    });
+    // A task waiting on a sleeper, which wakes it after it was aborted.
+    let sleeper = AsyncThreadSleeper::with_timer(Duration::from_secs(1), &driver.handle());
+    let sleeping = {
+        let flag = DropFlag(dropped.clone());
+        spawner.spawn(async move {
+            let _flag = flag;
+            sleeper.await;
+            panic!("The aborted task was polled again.");
+        })
+    };
+    let finished = spawner.spawn(async { 5 });
+
+    let checked = spawner.spawn(async move {
+        spinning.abort();
+        sleeping.abort_handle().abort();
+        assert!(spinning.await.unwrap_err().is_cancelled());
+        assert!(sleeping.await.unwrap_err().is_cancelled());
+
+        // Aborting a finished task does nothing.
+        let abort_handle = finished.abort_handle();
+        assert_eq!(finished.await.unwrap(), 5);
+        abort_handle.abort();
+        clock.advance(Duration::from_secs(1));
+        driver.turn();
+    });
+    drop(spawner);
+
+    // Both aborted tasks were dropped, or the executor would never finish.
+    executor.run();
+    block_on(checked).unwrap();
+    assert!(dropped.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_cancellation_token() {
+    let (executor, spawner) = Executor::with_workers(2);
+    let token = CancellationToken::new();
+    let child = token.child_token();
+    let grandchild = child.child_token();
+    let sibling = token.child_token();
+
+    // A child can be cancelled on its own.
+    sibling.cancel();
+    assert!(sibling.is_cancelled());
+    assert!(!token.is_cancelled());
+
+    let workers: Vec<_> = [token.clone(), child.clone(), grandchild.clone()]
+        .into_iter()
+        .map(|token| {
+            spawner.spawn(async move {
+                let work = async {
+                    loop {
+                        yield_now().await;
+                    }
+                };
+                let output: Option<()> = token.run_until_cancelled(work).await;
+                token.cancelled().await;
+                output
+            })
+        })
+        .collect();
+    let finished = {
+        let token = token.clone();
+        spawner.spawn(async move { token.run_until_cancelled(async { 3 }).await })
+    };
+    // A token that is never cancelled doesn't keep the task from finishing.
+    let never_cancelled = {
+        let never = CancellationToken::new();
+        spawner.spawn(async move {
+            let output = never.run_until_cancelled(yield_now()).await;
+            assert_eq!(output, Some(()));
+            assert!(never.lock().wakers.is_empty());
+        })
+    };
+
+    let checked = spawner.spawn(async move {
+        assert_eq!(finished.await.unwrap(), Some(3));
+        token.cancel();
+        for worker in workers {
+            assert_eq!(worker.await.unwrap(), None);
+        }
+        assert!(grandchild.is_cancelled());
+        assert!(token.child_token().is_cancelled());
+    });
+    drop(spawner);
+    executor.run();
+    block_on(never_cancelled).unwrap();
+    block_on(checked).unwrap();
+}
@@ -0,0 +1,49 @@
+//! Channels for sending values between tasks, which wait by suspending the task rather than
+//! blocking its thread.
+//!
+//! - [mpsc]: any number of senders and one receiver, either bounded or unbounded.
+//! - [oneshot]: a single value, from one sender to one receiver.
+//! - [broadcast]: every receiver gets every value, as long as it keeps up.
+//! - [watch]: receivers see the latest value, and can wait for it to change.
+//!
+//! A channel is closed once every sender or every receiver is gone. Values that were already
+//! sent can still be received after the senders are gone.
+#![allow(dead_code)]
+
+mod broadcast;
+mod mpsc;
+mod oneshot;
+mod watch;
+
+use std::fmt;
+
+/// The value couldn't be sent, as there is nothing left to receive it. The value is handed
+/// back.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// Nothing can be received, as the sender is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl std::error::Error for RecvError {}
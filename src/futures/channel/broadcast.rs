@@ -0,0 +1,340 @@
+//! A channel where every receiver gets a clone of every value. The channel keeps the last
+//! `capacity` values, and a receiver that falls further behind than that skips ahead to the
+//! oldest one that is left, with an error that says how many it missed.
+
+use super::SendError;
+use crate::futures::WaitList;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+};
+
+struct Shared<T> {
+    buffer: VecDeque<T>,
+    capacity: usize,
+    /// The position of the oldest value in the buffer, counting every value ever sent.
+    head: u64,
+    senders: usize,
+    receivers: usize,
+    /// The receivers waiting for the next value.
+    wakers: WaitList,
+}
+
+impl<T> Shared<T> {
+    /// The position that the next value will be sent at.
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+
+    /// The value or error for a receiver at `next`, or `None` when there isn't one yet.
+    fn take(&self, next: &mut u64) -> Option<Result<T, RecvError>>
+    where
+        T: Clone,
+    {
+        if *next < self.head {
+            let skipped = self.head - *next;
+            *next = self.head;
+            return Some(Err(RecvError::Lagged(skipped)));
+        }
+        if *next < self.tail() {
+            let value = self.buffer[(*next - self.head) as usize].clone();
+            *next += 1;
+            return Some(Ok(value));
+        }
+        if self.senders == 0 {
+            return Some(Err(RecvError::Closed));
+        }
+        None
+    }
+}
+
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(
+        capacity > 0,
+        "The channel needs room for at least one value."
+    );
+    let shared = Arc::new(Mutex::new(Shared {
+        buffer: VecDeque::with_capacity(capacity),
+        capacity,
+        head: 0,
+        senders: 1,
+        receivers: 1,
+        wakers: WaitList::default(),
+    }));
+    let sender = Sender {
+        shared: shared.clone(),
+    };
+    (sender, Receiver { shared, next: 0 })
+}
+
+fn lock<T>(shared: &Mutex<Shared<T>>) -> MutexGuard<'_, Shared<T>> {
+    shared.lock().expect("Failed to lock the channel.")
+}
+
+/// Sends values to every receiver. The channel closes once every clone is dropped.
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        lock(&self.shared).senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            shared.wakers.wake_all();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Send the value to every receiver, and return how many there are. The value is handed
+    /// back when there are none. Sending never waits, as the oldest value is dropped to make
+    /// room.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let (receivers, evicted) = {
+            let mut shared = lock(&self.shared);
+            if shared.receivers == 0 {
+                return Err(SendError(value));
+            }
+            shared.buffer.push_back(value);
+            let evicted = if shared.buffer.len() > shared.capacity {
+                shared.head += 1;
+                shared.buffer.pop_front()
+            } else {
+                None
+            };
+            shared.wakers.wake_all();
+            (shared.receivers, evicted)
+        };
+        // The value could do anything when it is dropped, so not under the lock.
+        drop(evicted);
+        Ok(receivers)
+    }
+
+    /// A receiver that gets the values sent from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut shared = lock(&self.shared);
+        shared.receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: shared.tail(),
+        }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        lock(&self.shared).receivers
+    }
+}
+
+/// Why [Receiver::recv] didn't return a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// The senders are gone, and the receiver has seen every value.
+    Closed,
+    /// The receiver missed this many values, and skipped ahead to the oldest one left.
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Closed => write!(f, "channel closed"),
+            RecvError::Lagged(skipped) => write!(f, "receiver lagged by {skipped} values"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// Why [Receiver::try_recv] didn't return a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The receiver has seen every value, but more could still be sent.
+    Empty,
+    Closed,
+    Lagged(u64),
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Closed => write!(f, "channel closed"),
+            TryRecvError::Lagged(skipped) => write!(f, "receiver lagged by {skipped} values"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+/// Gets every value sent after it subscribed. A clone starts from the same place.
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    /// The position of the next value to receive.
+    next: u64,
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        lock(&self.shared).receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        lock(&self.shared).receivers -= 1;
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Wait for the next value. A receiver that fell behind gets [RecvError::Lagged] first,
+    /// and then the oldest value that is left.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv {
+            receiver: self,
+            id: None,
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let shared = lock(&self.shared);
+        match shared.take(&mut self.next) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(RecvError::Closed)) => Err(TryRecvError::Closed),
+            Some(Err(RecvError::Lagged(skipped))) => Err(TryRecvError::Lagged(skipped)),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// Resolves to the next value for its receiver.
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    /// The id of its waker, while it waits for a value.
+    id: Option<u64>,
+}
+
+impl<T: Clone> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let receiver = &mut *this.receiver;
+        let mut shared = lock(&receiver.shared);
+        if let Some(result) = shared.take(&mut receiver.next) {
+            if let Some(id) = this.id.take() {
+                shared.wakers.remove(id);
+            }
+            return Poll::Ready(result);
+        }
+        shared.wakers.register(&mut this.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            if let Ok(mut shared) = self.receiver.shared.lock() {
+                shared.wakers.remove(id);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_broadcast() {
+    use crate::futures::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+    let (sender, receiver) = channel(64);
+
+    // Every receiver gets every value, and then hears that the channel is closed.
+    let receivers: Vec<_> = (0..3)
+        .map(|_| {
+            let mut receiver = receiver.clone();
+            spawner.spawn(async move {
+                let mut values = Vec::new();
+                loop {
+                    match receiver.recv().await {
+                        Ok(value) => values.push(value),
+                        Err(error) => {
+                            assert_eq!(error, RecvError::Closed);
+                            return values;
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(receiver);
+    spawner.spawn(async move {
+        for i in 0..50 {
+            assert_eq!(sender.send(i).unwrap(), 3);
+            yield_now().await;
+        }
+    });
+
+    executor.block_on(async {
+        for receiver in receivers {
+            assert_eq!(receiver.await.unwrap(), (0..50).collect::<Vec<_>>());
+        }
+    });
+}
+
+#[test]
+fn test_broadcast_lag() {
+    use crate::futures::block_on;
+    let (sender, mut receiver) = channel(2);
+    let mut late = receiver.clone();
+    for i in 1..=5 {
+        sender.send(i).unwrap();
+    }
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Lagged(3)));
+    assert_eq!(receiver.try_recv(), Ok(4));
+    assert_eq!(receiver.try_recv(), Ok(5));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+    assert_eq!(block_on(late.recv()), Err(RecvError::Lagged(3)));
+    assert_eq!(block_on(late.recv()), Ok(4));
+
+    // A subscriber only gets what is sent after it subscribed.
+    let mut subscriber = sender.subscribe();
+    assert_eq!(sender.send(6).unwrap(), 3);
+    assert_eq!(subscriber.try_recv(), Ok(6));
+
+    // Polling a waiting receiver again doesn't add another waker.
+    let mut waiting = subscriber.recv();
+    let waker = futures::task::noop_waker();
+    let context = &mut Context::from_waker(&waker);
+    assert!(Pin::new(&mut waiting).poll(context).is_pending());
+    assert!(Pin::new(&mut waiting).poll(context).is_pending());
+    assert_eq!(lock(&sender.shared).wakers.len(), 1);
+    drop(waiting);
+    assert!(lock(&sender.shared).wakers.is_empty());
+
+    drop(sender);
+    assert_eq!(block_on(late.recv()), Ok(5));
+    assert_eq!(block_on(late.recv()), Ok(6));
+    assert_eq!(block_on(late.recv()), Err(RecvError::Closed));
+
+    let (sender, receiver) = channel(1);
+    drop(receiver);
+    assert_eq!(sender.send("lost"), Err(SendError("lost")));
+}
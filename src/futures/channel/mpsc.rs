@@ -0,0 +1,496 @@
+//! A queue from any number of senders to one receiver. A bounded channel makes senders wait
+//! while it is full, in the order that they started waiting, while an unbounded one never
+//! does.
+
+use super::SendError;
+use crate::futures::WaitList;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+struct Chan<T> {
+    queue: VecDeque<T>,
+    /// There is no limit for an unbounded channel.
+    capacity: Option<usize>,
+    senders: usize,
+    /// Set once the receiver is closed or dropped.
+    closed: bool,
+    recv_waker: Option<Waker>,
+    /// The senders waiting for room in a bounded channel.
+    send_wakers: WaitList,
+    /// The room held for senders that were woken, but haven't been polled yet.
+    reserved: usize,
+}
+
+impl<T> Chan<T> {
+    fn has_room(&self) -> bool {
+        self.capacity
+            .is_none_or(|capacity| self.queue.len() + self.reserved < capacity)
+    }
+
+    /// Hand the room to the waiting senders, in order. A woken sender is out of the list,
+    /// and its room is held for it, so that a sender that arrives later can't take it first.
+    fn assign(&mut self) {
+        while self.has_room() && self.send_wakers.wake_one() {
+            self.reserved += 1;
+        }
+    }
+
+    fn push(&mut self, value: T) {
+        self.queue.push_back(value);
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn new<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Mutex::new(Chan {
+        queue: VecDeque::new(),
+        capacity,
+        senders: 1,
+        closed: false,
+        recv_waker: None,
+        send_wakers: WaitList::default(),
+        reserved: 0,
+    }));
+    let sender = Sender { chan: chan.clone() };
+    (sender, Receiver { chan })
+}
+
+/// A channel that holds up to `capacity` values that haven't been received yet.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(
+        capacity > 0,
+        "The channel needs room for at least one value."
+    );
+    new(Some(capacity))
+}
+
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let (sender, receiver) = new(None);
+    (UnboundedSender { sender }, receiver)
+}
+
+fn lock<T>(chan: &Mutex<Chan<T>>) -> MutexGuard<'_, Chan<T>> {
+    chan.lock().expect("Failed to lock the channel.")
+}
+
+/// Sends values to a bounded channel. The channel closes for the receiver once every clone
+/// is dropped.
+pub struct Sender<T> {
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        lock(&self.chan).senders += 1;
+        Sender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut chan = lock(&self.chan);
+        chan.senders -= 1;
+        if chan.senders == 0 {
+            if let Some(waker) = chan.recv_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Wait for room in the channel, and then queue the value. The value is handed back when
+    /// the receiver is gone.
+    pub fn send(&self, value: T) -> Sending<'_, T> {
+        Sending {
+            sender: self,
+            value: Some(value),
+            id: None,
+        }
+    }
+
+    /// Queue the value if there is room for it right now.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut chan = lock(&self.chan);
+        if chan.closed {
+            Err(TrySendError::Closed(value))
+        } else if !chan.has_room() {
+            Err(TrySendError::Full(value))
+        } else {
+            chan.push(value);
+            Ok(())
+        }
+    }
+
+    /// Whether the receiver is closed or gone.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.chan).closed
+    }
+}
+
+/// Resolves once the value is queued, or the receiver is gone.
+pub struct Sending<'a, T> {
+    sender: &'a Sender<T>,
+    /// Taken once the future resolves.
+    value: Option<T>,
+    /// The id of its waker, while it waits for room.
+    id: Option<u64>,
+}
+
+/// The value is never pinned, only moved into the channel.
+impl<T> Unpin for Sending<'_, T> {}
+
+impl<T> Future for Sending<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut chan = lock(&this.sender.chan);
+        if chan.closed {
+            if let Some(id) = this.id.take() {
+                chan.send_wakers.remove(id);
+            }
+            let value = this.value.take().expect("Sending polled after completion");
+            return Poll::Ready(Err(SendError(value)));
+        }
+        // A sender that was woken has room held for it.
+        let woken = this.id.is_some_and(|id| !chan.send_wakers.contains(id));
+        if woken {
+            chan.reserved -= 1;
+        }
+        if woken || chan.has_room() {
+            this.id = None;
+            let value = this.value.take().expect("Sending polled after completion");
+            chan.push(value);
+            return Poll::Ready(Ok(()));
+        }
+        chan.send_wakers.register(&mut this.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+/// A sender that was woken for room, but is dropped before taking it, passes the room on
+/// to the next one.
+impl<T> Drop for Sending<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            if let Ok(mut chan) = self.sender.chan.lock() {
+                if !chan.send_wakers.remove(id) && !chan.closed {
+                    chan.reserved -= 1;
+                    chan.assign();
+                }
+            }
+        }
+    }
+}
+
+/// Sends values to an unbounded channel, which never has to wait.
+pub struct UnboundedSender<T> {
+    sender: Sender<T>,
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        UnboundedSender {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Queue the value, or hand it back when the receiver is gone.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.try_send(value).map_err(|error| match error {
+            TrySendError::Full(value) | TrySendError::Closed(value) => SendError(value),
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+/// Why [Sender::try_send] couldn't queue the value, which is handed back.
+#[derive(PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "Full(..)"),
+            TrySendError::Closed(_) => write!(f, "Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "channel full"),
+            TrySendError::Closed(_) => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl<T> std::error::Error for TrySendError<T> {}
+
+/// Why [Receiver::try_recv] didn't have a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// Nothing is queued, but more could still be sent.
+    Empty,
+    /// Nothing is queued, and the senders are gone or the receiver was closed.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Disconnected => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+/// Takes the values from the channel, in the order that they were queued.
+pub struct Receiver<T> {
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Wait for the next value. There is none once the channel is empty and closed.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut chan = lock(&self.chan);
+        match chan.queue.pop_front() {
+            Some(value) => {
+                chan.assign();
+                Ok(value)
+            }
+            None if chan.closed || chan.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Stop any more values from being sent, while still receiving the ones that were
+    /// already queued.
+    pub fn close(&mut self) {
+        let mut chan = lock(&self.chan);
+        chan.closed = true;
+        chan.send_wakers.wake_all();
+    }
+}
+
+/// Closes the channel, and drops the values that were never received.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let queue = match self.chan.lock() {
+            Ok(mut chan) => {
+                chan.closed = true;
+                chan.recv_waker = None;
+                chan.send_wakers.wake_all();
+                mem::take(&mut chan.queue)
+            }
+            Err(_) => return,
+        };
+        // The values could do anything when they are dropped, so not under the lock.
+        drop(queue);
+    }
+}
+
+/// Resolves to the next value, or `None` once the channel is empty and closed.
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut chan = lock(&self.receiver.chan);
+        if let Some(value) = chan.queue.pop_front() {
+            chan.assign();
+            return Poll::Ready(Some(value));
+        }
+        if chan.closed || chan.senders == 0 {
+            return Poll::Ready(None);
+        }
+        if !chan
+            .recv_waker
+            .as_ref()
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            chan.recv_waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        if let Ok(mut chan) = self.receiver.chan.lock() {
+            chan.recv_waker = None;
+        }
+    }
+}
+
+#[test]
+fn test_mpsc_bounded() {
+    use crate::futures::Executor;
+    let (executor, spawner) = Executor::new();
+    let (sender, mut receiver) = channel(2);
+
+    // The producers have to wait on the consumer, as the channel only holds two values.
+    let producers: Vec<_> = (0..3)
+        .map(|producer| {
+            let sender = sender.clone();
+            spawner.spawn(async move {
+                for i in 0..100 {
+                    sender.send((producer, i)).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let received = executor.block_on(async {
+        let mut received = Vec::new();
+        // The channel closes once every producer is done.
+        while let Some(value) = receiver.recv().await {
+            received.push(value);
+        }
+        for producer in producers {
+            producer.await.unwrap();
+        }
+        received
+    });
+    assert_eq!(received.len(), 300);
+    for producer in 0..3 {
+        let values: Vec<_> = received
+            .iter()
+            .filter(|(from, _)| *from == producer)
+            .map(|(_, i)| *i)
+            .collect();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_mpsc_close() {
+    use crate::futures::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+
+    executor.block_on(async {
+        let (sender, mut receiver) = channel(2);
+        sender.try_send(1).unwrap();
+        sender.try_send(2).unwrap();
+        assert_eq!(sender.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(receiver.try_recv(), Ok(1));
+
+        // Closing keeps the queued values, but stops any more from being sent.
+        receiver.close();
+        assert!(sender.is_closed());
+        assert_eq!(sender.try_send(3), Err(TrySendError::Closed(3)));
+        assert_eq!(sender.send(3).await, Err(SendError(3)));
+        assert_eq!(receiver.recv().await, Some(2));
+        assert_eq!(receiver.recv().await, None);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+
+        // A waiting sender is woken when the receiver is dropped.
+        let (sender, receiver) = channel(1);
+        sender.try_send(0).unwrap();
+        let waiting = spawner.spawn(async move { sender.send(1).await });
+        while lock(&receiver.chan).send_wakers.is_empty() {
+            yield_now().await;
+        }
+        drop(receiver);
+        assert_eq!(waiting.await.unwrap(), Err(SendError(1)));
+
+        // A sender that is woken for room but dropped hands the room on to the next one, or
+        // the second sender would never finish.
+        let (sender, mut receiver) = channel(1);
+        sender.try_send(0).unwrap();
+        let mut first = sender.send(1);
+        assert!(futures::poll!(&mut first).is_pending());
+        let second = spawner.spawn({
+            let sender = sender.clone();
+            async move { sender.send(2).await.unwrap() }
+        });
+        while lock(&sender.chan).send_wakers.len() < 2 {
+            yield_now().await;
+        }
+        assert_eq!(receiver.recv().await, Some(0));
+        drop(first);
+        second.await.unwrap();
+        assert_eq!(receiver.recv().await, Some(2));
+    });
+}
+
+#[test]
+fn test_mpsc_fairness() {
+    use crate::futures::block_on;
+    block_on(async {
+        let (sender, mut receiver) = channel(1);
+        sender.try_send(0).unwrap();
+        let mut first = sender.send(1);
+        assert!(futures::poll!(&mut first).is_pending());
+
+        // The room goes to the sender that was waiting, even before it is polled again.
+        assert_eq!(receiver.try_recv(), Ok(0));
+        assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+        let mut second = sender.send(3);
+        assert!(futures::poll!(&mut second).is_pending());
+        assert_eq!(futures::poll!(&mut first), Poll::Ready(Ok(())));
+
+        assert_eq!(receiver.recv().await, Some(1));
+        assert_eq!(second.await, Ok(()));
+        assert_eq!(receiver.recv().await, Some(3));
+        assert_eq!(lock(&receiver.chan).reserved, 0);
+    });
+}
+
+#[test]
+fn test_mpsc_unbounded() {
+    use crate::futures::Executor;
+    let (executor, spawner) = Executor::new();
+    let (sender, mut receiver) = unbounded_channel();
+
+    for i in 0..10 {
+        let sender = sender.clone();
+        spawner.spawn(async move { sender.send(i).unwrap() });
+    }
+    drop(sender);
+    let mut received = executor.block_on(async {
+        let mut received = Vec::new();
+        while let Some(i) = receiver.recv().await {
+            received.push(i);
+        }
+        received
+    });
+    received.sort();
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+
+    let (sender, receiver) = unbounded_channel();
+    drop(receiver);
+    assert!(sender.is_closed());
+    assert_eq!(sender.send("lost"), Err(SendError("lost")));
+}
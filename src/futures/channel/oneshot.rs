@@ -0,0 +1,168 @@
+//! A channel for a single value, which is an [AsyncState] with each side able to tell when
+//! the other one is gone.
+
+use super::{RecvError, SendError};
+use crate::futures::AsyncState;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+};
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let state = Arc::new(Mutex::new(AsyncState::new()));
+    let sender = Sender {
+        state: Some(state.clone()),
+    };
+    (sender, Receiver { state })
+}
+
+fn lock<T>(state: &Mutex<AsyncState<T>>) -> MutexGuard<'_, AsyncState<T>> {
+    state.lock().expect("Failed to lock the oneshot state.")
+}
+
+/// Sends the value. Dropping it without sending closes the channel.
+pub struct Sender<T> {
+    /// Taken once the value is sent.
+    state: Option<Arc<Mutex<AsyncState<T>>>>,
+}
+
+impl<T> Sender<T> {
+    /// Hand the value to the receiver, or back to the caller when the receiver is gone.
+    pub fn send(mut self, value: T) -> Result<(), SendError<T>> {
+        let state = self.state.take().expect("The value was already sent.");
+        let mut state = lock(&state);
+        if state.closed {
+            return Err(SendError(value));
+        }
+        state.complete(value);
+        Ok(())
+    }
+
+    /// Whether the receiver is gone.
+    pub fn is_closed(&self) -> bool {
+        self.state.as_ref().is_some_and(|state| lock(state).closed)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            if let Ok(mut state) = state.lock() {
+                state.close();
+            }
+        }
+    }
+}
+
+/// Why there was nothing to receive yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The value hasn't been sent yet.
+    Empty,
+    /// The sender is gone without sending anything, or the value was already received.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Closed => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+/// Resolves to the value, or an error when the sender is dropped without sending one.
+pub struct Receiver<T> {
+    state: Arc<Mutex<AsyncState<T>>>,
+}
+
+impl<T> Receiver<T> {
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = lock(&self.state);
+        match state.result.take() {
+            Some(value) => {
+                state.closed = true;
+                Ok(value)
+            }
+            None if state.closed => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.state);
+        if let Some(value) = state.result.take() {
+            state.closed = true;
+            return Poll::Ready(Ok(value));
+        }
+        if state.closed {
+            return Poll::Ready(Err(RecvError));
+        }
+        if !state
+            .waker
+            .as_ref()
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            state.waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Let the sender know that nothing will receive its value, and let go of the task's waker.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            state.waker = None;
+        }
+    }
+}
+
+#[test]
+fn test_oneshot() {
+    use crate::futures::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+
+    executor.block_on(async {
+        // The value is handed from one task to another.
+        let (sender, receiver) = channel();
+        spawner.spawn(async move {
+            yield_now().await;
+            assert!(!sender.is_closed());
+            sender.send(String::from("hello")).unwrap();
+        });
+        assert_eq!(receiver.await.unwrap(), "hello");
+
+        // The receiver hears about a sender that was dropped without sending.
+        let (sender, receiver) = channel::<u32>();
+        spawner.spawn(async move {
+            yield_now().await;
+            drop(sender);
+        });
+        assert_eq!(receiver.await, Err(RecvError));
+
+        // The sender gets its value back when the receiver is gone.
+        let (sender, receiver) = channel();
+        let dropped = spawner.spawn(async move { drop(receiver) });
+        dropped.await.unwrap();
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(5), Err(SendError(5)));
+    });
+
+    let (sender, mut receiver) = channel();
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+    sender.send(7).unwrap();
+    assert_eq!(receiver.try_recv(), Ok(7));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+}
@@ -0,0 +1,311 @@
+//! A channel that holds a single value, which the sender replaces. Receivers can look at the
+//! latest value at any time, or wait for it to change. A receiver that is slow to look only
+//! sees the latest value, rather than every one in between.
+
+use super::{RecvError, SendError};
+use crate::futures::WaitList;
+use std::{
+    future::Future,
+    mem,
+    ops::Deref,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    task::{Context, Poll},
+};
+
+/// The value has its own lock, so that any number of [Ref]s can share it. It is always
+/// locked before the state, and the version changes while the value is locked for writing.
+struct Shared<T> {
+    value: RwLock<T>,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Counts the changes to the value.
+    version: u64,
+    sender_alive: bool,
+    receivers: usize,
+    /// The receivers waiting for the value to change.
+    wakers: WaitList,
+}
+
+pub fn channel<T>(initial: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(initial),
+        state: Mutex::new(State {
+            version: 0,
+            sender_alive: true,
+            receivers: 1,
+            wakers: WaitList::default(),
+        }),
+    });
+    let sender = Sender {
+        shared: shared.clone(),
+    };
+    (sender, Receiver { shared, seen: 0 })
+}
+
+impl<T> Shared<T> {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Failed to lock the channel.")
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.value
+            .read()
+            .expect("Failed to lock the channel value.")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.value
+            .write()
+            .expect("Failed to lock the channel value.")
+    }
+}
+
+/// The value in the channel, which the sender can't change while this is held. It must be
+/// dropped before awaiting, as a sender on the same thread would never get to change it.
+pub struct Ref<'a, T> {
+    value: RwLockReadGuard<'a, T>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Changes the value. Dropping it closes the channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        state.sender_alive = false;
+        state.wakers.wake_all();
+    }
+}
+
+impl<T> Sender<T> {
+    /// Replace the value, and wake the receivers. The value is handed back when there are
+    /// no receivers.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let old = {
+            let mut current = self.shared.write();
+            let mut state = self.shared.state();
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+            let old = mem::replace(&mut *current, value);
+            state.version += 1;
+            state.wakers.wake_all();
+            old
+        };
+        // The old value could do anything when it is dropped, so not under the lock.
+        drop(old);
+        Ok(())
+    }
+
+    /// Change the value in place, even when there are no receivers.
+    pub fn send_modify(&self, modify: impl FnOnce(&mut T)) {
+        let mut current = self.shared.write();
+        modify(&mut current);
+        let mut state = self.shared.state();
+        state.version += 1;
+        state.wakers.wake_all();
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            value: self.shared.read(),
+        }
+    }
+
+    /// A receiver that has already seen the current value.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.state();
+        state.receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            seen: state.version,
+        }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.shared.state().receivers
+    }
+
+    /// Whether every receiver is gone.
+    pub fn is_closed(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+/// Looks at the value, and waits for it to change. A clone has seen the same changes.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// The version of the value that the receiver last looked at.
+    seen: u64,
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.state().receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state().receivers -= 1;
+    }
+}
+
+impl<T> Receiver<T> {
+    /// The latest value, without marking it as seen.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            value: self.shared.read(),
+        }
+    }
+
+    /// The latest value, which is marked as seen.
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let value = self.shared.read();
+        self.seen = self.shared.state().version;
+        Ref { value }
+    }
+
+    pub fn has_changed(&self) -> bool {
+        self.shared.state().version != self.seen
+    }
+
+    /// Wait until there is a value that hasn't been seen, and mark it as seen. This fails
+    /// once the sender is gone, unless there is still a change to see.
+    pub fn changed(&mut self) -> Changed<'_, T> {
+        Changed {
+            receiver: self,
+            id: None,
+        }
+    }
+}
+
+/// Resolves once the value changes, or the sender is gone.
+pub struct Changed<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    /// The id of its waker, while it waits for a change.
+    id: Option<u64>,
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let receiver = &mut *this.receiver;
+        let mut state = receiver.shared.state();
+        let result = if state.version != receiver.seen {
+            receiver.seen = state.version;
+            Ok(())
+        } else if !state.sender_alive {
+            Err(RecvError)
+        } else {
+            state.wakers.register(&mut this.id, cx.waker());
+            return Poll::Pending;
+        };
+        if let Some(id) = this.id.take() {
+            state.wakers.remove(id);
+        }
+        Poll::Ready(result)
+    }
+}
+
+impl<T> Drop for Changed<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            if let Ok(mut state) = self.receiver.shared.state.lock() {
+                state.wakers.remove(id);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_watch() {
+    use crate::futures::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+    let (sender, receiver) = channel(0);
+
+    // Each receiver sees the value go up, though it can miss some of the steps, and always
+    // sees the last one before the sender is dropped.
+    let receivers: Vec<_> = (0..3)
+        .map(|_| {
+            let mut receiver = receiver.clone();
+            spawner.spawn(async move {
+                let mut seen = Vec::new();
+                while receiver.changed().await.is_ok() {
+                    seen.push(*receiver.borrow());
+                }
+                assert!(!receiver.has_changed());
+                seen
+            })
+        })
+        .collect();
+    spawner.spawn(async move {
+        for i in 1..=100 {
+            sender.send(i).unwrap();
+            yield_now().await;
+        }
+    });
+
+    executor.block_on(async {
+        for receiver in receivers {
+            let seen = receiver.await.unwrap();
+            assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(seen.last(), Some(&100));
+        }
+    });
+    assert_eq!(*receiver.borrow(), 100);
+}
+
+#[test]
+fn test_watch_versions() {
+    use crate::futures::block_on;
+    let (sender, mut receiver) = channel(String::from("a"));
+    assert!(!receiver.has_changed());
+
+    sender.send_modify(|value| value.push('b'));
+    let mut subscriber = sender.subscribe();
+    assert!(receiver.has_changed());
+    assert!(!subscriber.has_changed());
+    assert_eq!(*receiver.borrow_and_update(), "ab");
+    assert!(!receiver.has_changed());
+
+    // A change that was made before the sender was dropped can still be seen.
+    sender.send(String::from("c")).unwrap();
+    assert_eq!(*sender.borrow(), "c");
+    drop(sender);
+    assert_eq!(block_on(subscriber.changed()), Ok(()));
+    assert_eq!(block_on(subscriber.changed()), Err(RecvError));
+    assert_eq!(block_on(receiver.changed()), Ok(()));
+
+    let (sender, receiver) = channel(1);
+    drop(receiver);
+    assert!(sender.is_closed());
+    assert_eq!(sender.send(2), Err(SendError(2)));
+    sender.send_modify(|value| *value = 3);
+    assert_eq!(*sender.borrow(), 3);
+
+    // Any number of borrows can be held at once on one thread.
+    let (sender, receiver) = channel(4);
+    let other = receiver.clone();
+    let (first, second, third) = (receiver.borrow(), other.borrow(), sender.borrow());
+    assert_eq!((*first, *second, *third), (4, 4, 4));
+}
@@ -0,0 +1,306 @@
+//! An executor for futures that aren't `Send`, such as ones that hold an `Rc`. The futures
+//! stay on the thread that runs the [LocalExecutor], and only their wakers, which just queue
+//! an id, are shared with other threads. That lets a local task wait on a task, a timer, or
+//! anything else that is running on another thread, including a multi-threaded
+//! [Executor](super::Executor).
+
+use super::{joinable, Abort, AbortHandle, JoinHandle};
+use futures::{
+    future::{FutureExt, LocalBoxFuture},
+    task::{waker_ref, ArcWake},
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    future::Future,
+    mem,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, Weak,
+    },
+    task::Context,
+};
+
+/// The ids of the local tasks that were woken, which is the only part of the executor that
+/// other threads can reach.
+struct ReadyQueue {
+    ids: Mutex<VecDeque<u64>>,
+    /// Signalled when an id is queued.
+    ready: Condvar,
+}
+
+impl ReadyQueue {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<u64>> {
+        self.ids.lock().expect("Failed to lock the ready queue.")
+    }
+
+    fn pop(&self) -> Option<u64> {
+        self.lock().pop_front()
+    }
+}
+
+/// The `Send` half of a local task, which its wakers and [AbortHandle] point to. Its future
+/// is kept by the executor under the same id.
+struct LocalTask {
+    id: u64,
+    /// Whether the id is in the ready queue, so that waking it many times only queues it once.
+    scheduled: AtomicBool,
+    aborted: AtomicBool,
+    queue: Arc<ReadyQueue>,
+}
+
+impl LocalTask {
+    fn schedule(&self) {
+        if !self.scheduled.swap(true, Ordering::AcqRel) {
+            self.queue.lock().push_back(self.id);
+            self.queue.ready.notify_one();
+        }
+    }
+}
+
+impl ArcWake for LocalTask {
+    /// Waking an aborted task does nothing, as it was already queued to drop its future.
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if !arc_self.aborted.load(Ordering::Acquire) {
+            arc_self.schedule();
+        }
+    }
+}
+
+impl Abort for LocalTask {
+    fn abort(self: Arc<Self>) {
+        if !self.aborted.swap(true, Ordering::AcqRel) {
+            self.schedule();
+        }
+    }
+}
+
+/// The tasks by their id, along with their futures.
+type LocalTasks = HashMap<u64, (Arc<LocalTask>, LocalBoxFuture<'static, ()>)>;
+
+struct LocalShared {
+    /// Every task other than the one that is being polled.
+    tasks: RefCell<LocalTasks>,
+    next_id: Cell<u64>,
+    queue: Arc<ReadyQueue>,
+}
+
+/// Runs the spawned tasks on the current thread, until there are none left that could still
+/// be woken. Unlike the [Executor](super::Executor), it doesn't wait for its spawners to be
+/// dropped, as they can only be used from this thread, which is busy running it.
+struct LocalExecutor {
+    shared: Rc<LocalShared>,
+}
+
+impl LocalExecutor {
+    fn new() -> (Self, LocalSpawner) {
+        let shared = Rc::new(LocalShared {
+            tasks: RefCell::new(HashMap::new()),
+            next_id: Cell::new(0),
+            queue: Arc::new(ReadyQueue {
+                ids: Mutex::new(VecDeque::new()),
+                ready: Condvar::new(),
+            }),
+        });
+        let spawner = LocalSpawner {
+            shared: shared.clone(),
+        };
+        (LocalExecutor { shared }, spawner)
+    }
+
+    /// Block until all of the tasks are done.
+    fn run(&self) {
+        loop {
+            if let Some(id) = self.shared.queue.pop() {
+                self.poll(id);
+                continue;
+            }
+            if self.drop_idle_tasks() {
+                // Dropping a future can wake the tasks that were waiting on it.
+                continue;
+            }
+            if self.shared.tasks.borrow().is_empty() {
+                return;
+            }
+            let ids = self.shared.queue.lock();
+            let _ids = self
+                .shared
+                .queue
+                .ready
+                .wait_while(ids, |ids| ids.is_empty())
+                .expect("Failed to lock the ready queue.");
+        }
+    }
+
+    fn poll(&self, id: u64) {
+        // The task is taken out of the map while it is polled, so that it can spawn others.
+        let Some((task, mut future)) = self.shared.tasks.borrow_mut().remove(&id) else {
+            return;
+        };
+        task.scheduled.store(false, Ordering::Release);
+        if task.aborted.load(Ordering::Acquire) {
+            return;
+        }
+        let waker = waker_ref(&task);
+        let context = &mut Context::from_waker(&waker);
+        if future.as_mut().poll(context).is_pending() {
+            self.shared.tasks.borrow_mut().insert(id, (task, future));
+        }
+    }
+
+    /// Drop the tasks that nothing holds a waker for, as they can never be woken again. This
+    /// is what dropping the last reference to a task does on the [Executor](super::Executor).
+    fn drop_idle_tasks(&self) -> bool {
+        let idle: Vec<_> = {
+            let mut tasks = self.shared.tasks.borrow_mut();
+            let ids: Vec<u64> = tasks
+                .iter()
+                .filter(|(_, (task, _))| {
+                    Arc::strong_count(task) == 1 && !task.scheduled.load(Ordering::Acquire)
+                })
+                .map(|(&id, _)| id)
+                .collect();
+            ids.iter().filter_map(|id| tasks.remove(id)).collect()
+        };
+        // The futures are dropped outside of the borrow, in case they spawn or wake a task.
+        let dropped = !idle.is_empty();
+        mem::drop(idle);
+        dropped
+    }
+}
+
+/// Takes a future that doesn't need to be `Send`, and queues it on the [LocalExecutor].
+#[derive(Clone)]
+struct LocalSpawner {
+    shared: Rc<LocalShared>,
+}
+
+impl LocalSpawner {
+    /// Queue the future on the executor. Its output, or its panic, goes to the returned
+    /// [JoinHandle], and the task keeps running if the handle is dropped.
+    fn spawn_local<T: 'static>(&self, future: impl Future<Output = T> + 'static) -> JoinHandle<T> {
+        let (future, join_state) = joinable(future);
+        let id = self.shared.next_id.get();
+        self.shared.next_id.set(id + 1);
+        let task = Arc::new(LocalTask {
+            id,
+            scheduled: AtomicBool::new(false),
+            aborted: AtomicBool::new(false),
+            queue: self.shared.queue.clone(),
+        });
+        let abort_handle = AbortHandle {
+            task: Arc::downgrade(&task) as Weak<dyn Abort>,
+        };
+        task.schedule();
+        self.shared
+            .tasks
+            .borrow_mut()
+            .insert(id, (task, future.boxed_local()));
+        JoinHandle {
+            join_state,
+            abort_handle,
+        }
+    }
+}
+
+#[test]
+fn test_local_executor() {
+    let (executor, spawner) = LocalExecutor::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    for index in 0..3 {
+        let log = log.clone();
+        let inner_spawner = spawner.clone();
+        spawner
+            .spawn_local(async move {
+                log.borrow_mut().push(index);
+                super::yield_now().await;
+                // The output doesn't have to be Send either.
+                let inner = inner_spawner.spawn_local(async move { Rc::new(index * 10) });
+                let value = inner.await.unwrap();
+                log.borrow_mut().push(*value);
+            })
+            .detach();
+    }
+    let panicked = spawner.spawn_local(async {
+        panic!("Local failure");
+    });
+    let checked = spawner.spawn_local(async move {
+        let error = panicked.await.unwrap_err();
+        assert_eq!(error.to_string(), "task panicked: Local failure");
+    });
+
+    executor.run();
+    super::block_on(checked).unwrap();
+    let mut log = log.take();
+    assert_eq!(log[..3], [0, 1, 2]);
+    log.sort();
+    assert_eq!(log, [0, 0, 1, 2, 10, 20]);
+}
+
+#[test]
+fn test_local_executor_alongside_executor() {
+    let (executor, spawner) = super::Executor::with_workers(2);
+    let (local_executor, local_spawner) = LocalExecutor::new();
+    let total = Rc::new(Cell::new(0));
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| executor.run());
+
+        for value in 1..=10 {
+            // The task runs on a worker, and wakes the local task from there.
+            let remote = spawner.spawn(async move {
+                super::timer::sleep(std::time::Duration::from_millis(value)).await;
+                value
+            });
+            let total = total.clone();
+            local_spawner
+                .spawn_local(async move {
+                    let value = remote.await.unwrap();
+                    total.set(total.get() + value);
+                })
+                .detach();
+        }
+        mem::drop(spawner);
+        local_executor.run();
+    });
+    assert_eq!(total.get(), 55);
+}
+
+#[test]
+fn test_local_abort() {
+    let (executor, spawner) = LocalExecutor::new();
+    let dropped = Rc::new(Cell::new(0));
+
+    /// Counts the futures that were dropped while they held it.
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    // One task is aborted, and the other can never be woken, so both are dropped.
+    let counter = DropCounter(dropped.clone());
+    let aborted = spawner.spawn_local(async move {
+        let _counter = counter;
+        futures::future::pending::<()>().await;
+    });
+    let counter = DropCounter(dropped.clone());
+    let forgotten = spawner.spawn_local(async move {
+        let _counter = counter;
+        futures::future::pending::<()>().await;
+    });
+    let abort_handle = aborted.abort_handle();
+    let checked = spawner.spawn_local(async move {
+        abort_handle.abort();
+        assert!(aborted.await.unwrap_err().is_cancelled());
+        assert!(forgotten.await.unwrap_err().is_cancelled());
+    });
+
+    executor.run();
+    super::block_on(checked).unwrap();
+    assert_eq!(dropped.get(), 2);
+}
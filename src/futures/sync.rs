@@ -0,0 +1,889 @@
+//! Locks and signals for tasks, which suspend the task while it waits rather than blocking
+//! its thread, so that a worker can run other tasks in the meantime.
+//!
+//! The [Semaphore] is fair: its permits go to the waiting tasks in the order that they
+//! started waiting, and a task that arrives later can't take them first. The [Mutex] and
+//! [RwLock] are semaphores underneath, so they are fair in the same way. A writer waiting on
+//! an [RwLock] keeps any more readers from getting in ahead of it.
+#![allow(dead_code)]
+
+use super::WaitList;
+use std::{
+    cell::UnsafeCell,
+    collections::VecDeque,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+/// A task waiting for permits.
+struct Waiter {
+    id: u64,
+    permits: usize,
+    waker: Waker,
+}
+
+struct SemaphoreState {
+    permits: usize,
+    waiters: VecDeque<Waiter>,
+    next_id: u64,
+}
+
+impl SemaphoreState {
+    /// Hand the permits to the waiters at the front of the queue, for as long as there are
+    /// enough for the next one. A waiter is done once it is out of the queue, so the permits
+    /// can't be taken by anyone else before it is polled.
+    fn assign(&mut self) {
+        while let Some(waiter) = self.waiters.front() {
+            if waiter.permits > self.permits {
+                break;
+            }
+            self.permits -= waiter.permits;
+            if let Some(waiter) = self.waiters.pop_front() {
+                waiter.waker.wake();
+            }
+        }
+    }
+}
+
+/// Hands out a limited number of permits.
+pub struct Semaphore {
+    state: StdMutex<SemaphoreState>,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            state: StdMutex::new(SemaphoreState {
+                permits,
+                waiters: VecDeque::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> StdMutexGuard<'_, SemaphoreState> {
+        self.state
+            .lock()
+            .expect("Failed to lock the semaphore state.")
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.lock().permits
+    }
+
+    /// Add permits, which go to the waiting tasks first.
+    pub fn add_permits(&self, permits: usize) {
+        let mut state = self.lock();
+        state.permits += permits;
+        state.assign();
+    }
+
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Wait until there are enough permits, and no task that started waiting earlier.
+    pub fn acquire_many(&self, permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits,
+            id: None,
+        }
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Take the permits if they are free right now, and no task is waiting for them.
+    pub fn try_acquire_many(&self, permits: usize) -> Option<SemaphorePermit<'_>> {
+        let mut state = self.lock();
+        if !state.waiters.is_empty() || state.permits < permits {
+            return None;
+        }
+        state.permits -= permits;
+        Some(SemaphorePermit {
+            semaphore: self,
+            permits,
+        })
+    }
+
+    /// A permit that holds on to the semaphore, so that it can be moved into a task.
+    pub async fn acquire_owned(self: Arc<Self>) -> OwnedSemaphorePermit {
+        self.acquire().await.forget();
+        OwnedSemaphorePermit {
+            semaphore: self,
+            permits: 1,
+        }
+    }
+
+    pub fn try_acquire_owned(self: Arc<Self>) -> Option<OwnedSemaphorePermit> {
+        self.try_acquire()?.forget();
+        Some(OwnedSemaphorePermit {
+            semaphore: self,
+            permits: 1,
+        })
+    }
+}
+
+/// Resolves to the permits once they are handed over.
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    /// The id of its place in the queue, while it waits.
+    id: Option<u64>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.semaphore.lock();
+        match this.id {
+            None if state.waiters.is_empty() && state.permits >= this.permits => {
+                state.permits -= this.permits;
+            }
+            None => {
+                state.next_id += 1;
+                let id = state.next_id;
+                state.waiters.push_back(Waiter {
+                    id,
+                    permits: this.permits,
+                    waker: cx.waker().clone(),
+                });
+                this.id = Some(id);
+                return Poll::Pending;
+            }
+            Some(id) => {
+                if let Some(waiter) = state.waiters.iter_mut().find(|waiter| waiter.id == id) {
+                    if !waiter.waker.will_wake(cx.waker()) {
+                        waiter.waker = cx.waker().clone();
+                    }
+                    return Poll::Pending;
+                }
+                // It is out of the queue, so the permits were handed to it.
+                this.id = None;
+            }
+        }
+        Poll::Ready(SemaphorePermit {
+            semaphore: this.semaphore,
+            permits: this.permits,
+        })
+    }
+}
+
+/// Give up the place in the queue, or give back the permits that were handed over but never
+/// taken. Either way the tasks behind it could now be able to go.
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        if let Ok(mut state) = self.semaphore.state.lock() {
+            match state.waiters.iter().position(|waiter| waiter.id == id) {
+                Some(index) => {
+                    state.waiters.remove(index);
+                }
+                None => state.permits += self.permits,
+            }
+            state.assign();
+        }
+    }
+}
+
+/// Gives its permits back to the semaphore when it is dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Keep the permits out of the semaphore for good.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+/// A permit that keeps its semaphore alive, rather than borrowing it.
+pub struct OwnedSemaphorePermit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl OwnedSemaphorePermit {
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+/// A lock that can be held across an await. It is a semaphore with one permit.
+pub struct Mutex<T: ?Sized> {
+    semaphore: Semaphore,
+    value: UnsafeCell<T>,
+}
+
+// The semaphore only lets one guard have the value at a time.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Mutex {
+            semaphore: Semaphore::new(1),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        self.semaphore.acquire().await.forget();
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.semaphore.try_acquire()?.forget();
+        Some(MutexGuard { mutex: self })
+    }
+
+    /// No lock is needed, as the borrow already makes sure there are no guards.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+// Sharing the guard shares the value.
+unsafe impl<T: ?Sized + Send + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.semaphore.add_permits(1);
+    }
+}
+
+/// How many readers an [RwLock] lets in at once. A writer takes all of the permits.
+const MAX_READERS: usize = (u32::MAX >> 3) as usize;
+
+/// A lock for any number of readers or one writer, which can be held across an await.
+pub struct RwLock<T: ?Sized> {
+    semaphore: Semaphore,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub fn new(value: T) -> Self {
+        RwLock {
+            semaphore: Semaphore::new(MAX_READERS),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Wait until there is no writer, and no writer waiting ahead of this reader.
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.semaphore.acquire().await.forget();
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Wait until every reader and writer ahead of this one is gone.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.semaphore.acquire_many(MAX_READERS).await.forget();
+        RwLockWriteGuard { lock: self }
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.semaphore.try_acquire()?.forget();
+        Some(RwLockReadGuard { lock: self })
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.semaphore.try_acquire_many(MAX_READERS)?.forget();
+        Some(RwLockWriteGuard { lock: self })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.semaphore.add_permits(1);
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+// Sharing the guard shares the value.
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.semaphore.add_permits(MAX_READERS);
+    }
+}
+
+/// How a waiting [Notified] future was notified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notification {
+    One,
+    All,
+}
+
+struct NotifiedWaiter {
+    id: u64,
+    waker: Waker,
+    notification: Option<Notification>,
+}
+
+struct NotifyState {
+    /// Set by [Notify::notify_one] when nothing was waiting, for the next task to take.
+    permit: bool,
+    /// Stay in the queue until they see their notification, so that one that is dropped
+    /// first can pass it on.
+    waiters: VecDeque<NotifiedWaiter>,
+    next_id: u64,
+}
+
+impl NotifyState {
+    fn notify_one(&mut self) {
+        let waiting = self
+            .waiters
+            .iter_mut()
+            .find(|waiter| waiter.notification.is_none());
+        match waiting {
+            Some(waiter) => {
+                waiter.notification = Some(Notification::One);
+                waiter.waker.wake_by_ref();
+            }
+            None => self.permit = true,
+        }
+    }
+}
+
+/// Wakes tasks without handing them any data.
+pub struct Notify {
+    state: StdMutex<NotifyState>,
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+impl Notify {
+    pub fn new() -> Self {
+        Notify {
+            state: StdMutex::new(NotifyState {
+                permit: false,
+                waiters: VecDeque::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> StdMutexGuard<'_, NotifyState> {
+        self.state.lock().expect("Failed to lock the notify state.")
+    }
+
+    /// Wake the task that has waited the longest. When none is waiting, the next one to wait
+    /// doesn't have to.
+    pub fn notify_one(&self) {
+        self.lock().notify_one();
+    }
+
+    /// Wake every task that is waiting right now. Tasks that wait afterwards aren't woken.
+    pub fn notify_waiters(&self) {
+        let mut state = self.lock();
+        for waiter in &mut state.waiters {
+            if waiter.notification.is_none() {
+                waiter.notification = Some(Notification::All);
+                waiter.waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// A future that resolves once it is notified. It only joins the queue when it is first
+    /// polled.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            id: None,
+            done: false,
+        }
+    }
+}
+
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    /// The id of its place in the queue, while it waits.
+    id: Option<u64>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(());
+        }
+        let mut state = this.notify.lock();
+        match this.id {
+            None if state.permit => state.permit = false,
+            None => {
+                state.next_id += 1;
+                let id = state.next_id;
+                state.waiters.push_back(NotifiedWaiter {
+                    id,
+                    waker: cx.waker().clone(),
+                    notification: None,
+                });
+                this.id = Some(id);
+                return Poll::Pending;
+            }
+            Some(id) => {
+                let index = state
+                    .waiters
+                    .iter()
+                    .position(|waiter| waiter.id == id)
+                    .expect("A waiting Notified is always in the queue.");
+                let waiter = &mut state.waiters[index];
+                if waiter.notification.is_none() {
+                    if !waiter.waker.will_wake(cx.waker()) {
+                        waiter.waker = cx.waker().clone();
+                    }
+                    return Poll::Pending;
+                }
+                state.waiters.remove(index);
+                this.id = None;
+            }
+        }
+        this.done = true;
+        Poll::Ready(())
+    }
+}
+
+/// Leave the queue, and pass a [Notify::notify_one] that wasn't seen on to the next task.
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        if let Ok(mut state) = self.notify.state.lock() {
+            if let Some(index) = state.waiters.iter().position(|waiter| waiter.id == id) {
+                let waiter = state.waiters.remove(index);
+                if waiter.is_some_and(|waiter| waiter.notification == Some(Notification::One)) {
+                    state.notify_one();
+                }
+            }
+        }
+    }
+}
+
+struct BarrierState {
+    arrived: usize,
+    /// Counts the times that the barrier has let its tasks through.
+    generation: u64,
+    wakers: WaitList,
+}
+
+/// Holds tasks back until a number of them are waiting, and then lets them all through at
+/// once. It can be used again afterwards.
+pub struct Barrier {
+    tasks: usize,
+    state: StdMutex<BarrierState>,
+}
+
+impl Barrier {
+    pub fn new(tasks: usize) -> Self {
+        assert!(tasks > 0, "The barrier needs at least one task.");
+        Barrier {
+            tasks,
+            state: StdMutex::new(BarrierState {
+                arrived: 0,
+                generation: 0,
+                wakers: WaitList::default(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> StdMutexGuard<'_, BarrierState> {
+        self.state
+            .lock()
+            .expect("Failed to lock the barrier state.")
+    }
+
+    /// Wait for the rest of the tasks. The task arrives when the future is first polled, and
+    /// still counts as having arrived if the future is dropped before the others get there.
+    pub fn wait(&self) -> BarrierWait<'_> {
+        BarrierWait {
+            barrier: self,
+            generation: None,
+            id: None,
+        }
+    }
+}
+
+/// Resolves to whether its task was the one that let the others through.
+pub struct BarrierWait<'a> {
+    barrier: &'a Barrier,
+    /// The generation that it arrived in, once it has been polled.
+    generation: Option<u64>,
+    id: Option<u64>,
+}
+
+impl Future for BarrierWait<'_> {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<bool> {
+        let this = self.get_mut();
+        let mut state = this.barrier.lock();
+        let generation = match this.generation {
+            Some(generation) => generation,
+            None => {
+                state.arrived += 1;
+                if state.arrived == this.barrier.tasks {
+                    state.arrived = 0;
+                    state.generation += 1;
+                    this.generation = Some(state.generation);
+                    state.wakers.wake_all();
+                    return Poll::Ready(true);
+                }
+                this.generation = Some(state.generation);
+                state.generation
+            }
+        };
+        if state.generation != generation {
+            if let Some(id) = this.id.take() {
+                state.wakers.remove(id);
+            }
+            return Poll::Ready(false);
+        }
+        state.wakers.register(&mut this.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl Drop for BarrierWait<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            if let Ok(mut state) = self.barrier.state.lock() {
+                state.wakers.remove(id);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mutex() {
+    use super::{yield_now, Executor};
+    // With one worker, a lock that blocked the thread would never be let go.
+    let (executor, spawner) = Executor::with_workers(1);
+    let mutex = Arc::new(Mutex::new(0));
+
+    let tasks: Vec<_> = (0..10)
+        .map(|_| {
+            let mutex = mutex.clone();
+            spawner.spawn(async move {
+                for _ in 0..100 {
+                    let mut count = mutex.lock().await;
+                    let seen = *count;
+                    yield_now().await;
+                    *count = seen + 1;
+                }
+            })
+        })
+        .collect();
+
+    executor.block_on(async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(*mutex.lock().await, 1000);
+
+        // The lock goes to the tasks in the order that they started waiting.
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let guard = order.lock().await;
+        let mut tasks = Vec::new();
+        for i in 0..5 {
+            let task_order = order.clone();
+            tasks.push(spawner.spawn(async move { task_order.lock().await.push(i) }));
+            while order.semaphore.lock().waiters.len() <= i {
+                yield_now().await;
+            }
+        }
+        assert!(order.try_lock().is_none());
+        drop(guard);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(*order.lock().await, [0, 1, 2, 3, 4]);
+    });
+}
+
+#[test]
+fn test_rwlock() {
+    use super::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+    let lock = Arc::new(RwLock::new(String::from("before")));
+
+    executor.block_on(async {
+        // Any number of readers at once, but no writer.
+        let first = lock.read().await;
+        let second = lock.read().await;
+        assert_eq!(*first, *second);
+        assert!(lock.try_write().is_none());
+
+        // A writer that is waiting keeps new readers out, so it can't be starved.
+        let writer = spawner.spawn({
+            let lock = lock.clone();
+            async move { *lock.write().await = String::from("after") }
+        });
+        while lock.semaphore.lock().waiters.is_empty() {
+            yield_now().await;
+        }
+        assert!(lock.try_read().is_none());
+        let reader = spawner.spawn({
+            let lock = lock.clone();
+            async move { lock.read().await.clone() }
+        });
+        drop(first);
+        drop(second);
+        writer.await.unwrap();
+        assert_eq!(reader.await.unwrap(), "after");
+    });
+    assert!(lock.try_write().is_some());
+}
+
+#[test]
+fn test_semaphore() {
+    use super::{yield_now, Executor};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let (executor, spawner) = Executor::new();
+    let semaphore = Arc::new(Semaphore::new(3));
+    let running = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+
+    // The owned permits go into the tasks, and at most three run at once.
+    let tasks: Vec<_> = (0..10)
+        .map(|_| {
+            let semaphore = semaphore.clone();
+            let running = running.clone();
+            let most = most.clone();
+            spawner.spawn(async move {
+                let permit = semaphore.acquire_owned().await;
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                yield_now().await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                drop(permit);
+            })
+        })
+        .collect();
+
+    executor.block_on(async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(most.load(Ordering::SeqCst) <= 3);
+        assert_eq!(semaphore.available_permits(), 3);
+
+        // A waiter for many permits isn't passed by later waiters for fewer.
+        semaphore.acquire_many(3).await.forget();
+        let mut many = semaphore.acquire_many(2);
+        assert!(futures::poll!(&mut many).is_pending());
+        let one = spawner.spawn({
+            let semaphore = semaphore.clone();
+            async move { semaphore.acquire().await.permits() }
+        });
+        while semaphore.lock().waiters.len() < 2 {
+            yield_now().await;
+        }
+        semaphore.add_permits(1);
+        assert!(semaphore.try_acquire().is_none());
+        assert_eq!(semaphore.lock().waiters.len(), 2);
+        semaphore.add_permits(2);
+        assert_eq!(many.await.permits(), 2);
+        assert_eq!(one.await.unwrap(), 1);
+        assert_eq!(semaphore.available_permits(), 3);
+
+        // Permits that were handed to a future that is then dropped go back.
+        let held = semaphore.clone().try_acquire_owned().unwrap();
+        let mut waiting = semaphore.acquire_many(3);
+        assert!(futures::poll!(&mut waiting).is_pending());
+        drop(held);
+        assert_eq!(semaphore.available_permits(), 0);
+        drop(waiting);
+        assert_eq!(semaphore.available_permits(), 3);
+
+        // Forgotten permits stay out until more are added.
+        semaphore.acquire().await.forget();
+        assert_eq!(semaphore.available_permits(), 2);
+        semaphore.add_permits(1);
+        assert_eq!(semaphore.available_permits(), 3);
+    });
+}
+
+#[test]
+fn test_notify() {
+    use super::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+    let notify = Arc::new(Notify::new());
+
+    executor.block_on(async {
+        // A notification with nobody waiting is kept for the next one to wait.
+        notify.notify_one();
+        notify.notified().await;
+
+        // Every task that is waiting is woken at once.
+        let tasks: Vec<_> = (0..3)
+            .map(|_| {
+                let notify = notify.clone();
+                spawner.spawn(async move { notify.notified().await })
+            })
+            .collect();
+        while notify.lock().waiters.len() < 3 {
+            yield_now().await;
+        }
+        notify.notify_waiters();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // Nothing was kept for later.
+        let mut later = notify.notified();
+        assert!(futures::poll!(&mut later).is_pending());
+
+        // A notification that reaches a dropped future goes on to the next one.
+        let mut next = notify.notified();
+        assert!(futures::poll!(&mut next).is_pending());
+        notify.notify_one();
+        drop(later);
+        assert!(futures::poll!(&mut next).is_ready());
+    });
+}
+
+#[test]
+fn test_barrier() {
+    use super::{yield_now, Executor};
+    let (executor, spawner) = Executor::new();
+    let barrier = Arc::new(Barrier::new(5));
+    let arrived = Arc::new(Mutex::new(0));
+
+    // Nobody gets through until all five are there, and then one of them is the leader.
+    let tasks: Vec<_> = (0..5)
+        .map(|_| {
+            let barrier = barrier.clone();
+            let arrived = arrived.clone();
+            spawner.spawn(async move {
+                let mut leaders = 0;
+                for round in 1..=3 {
+                    *arrived.lock().await += 1;
+                    yield_now().await;
+                    if barrier.wait().await {
+                        leaders += 1;
+                    }
+                    assert!(*arrived.lock().await >= round * 5);
+                }
+                leaders
+            })
+        })
+        .collect();
+
+    let leaders = executor.block_on(async {
+        let mut leaders = 0;
+        for task in tasks {
+            leaders += task.await.unwrap();
+        }
+        leaders
+    });
+    assert_eq!(leaders, 3);
+}
@@ -0,0 +1,636 @@
+//! A single timer driver for all of the sleeps, intervals and timeouts, rather than a thread
+//! for each one. The timers are kept in a hierarchical timing wheel, with 6 levels of 64
+//! slots. Each level's slots are 64 times wider than the level below it, from a millisecond
+//! up to about 2.2 years for the whole wheel. A timer goes into the level where its deadline
+//! first differs from the current time, and moves down a level each time its slot comes up,
+//! until it fires from the bottom level on its exact millisecond.
+//!
+//! The driver either runs on its own background thread, or is turned by hand with
+//! [TimerDriver::turn], which is how a [MockClock](crate::clock::MockClock) drives it.
+
+use super::AsyncState;
+use crate::clock::{Clock, SystemClock};
+use std::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock},
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+const LEVELS: usize = 6;
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+/// Deadlines further out than this are put at the end of the wheel, and moved back into it
+/// as the time gets closer.
+const MAX_DURATION: u64 = 1 << (SLOT_BITS * LEVELS);
+
+struct Entry {
+    /// The milliseconds since the driver started.
+    deadline: u64,
+    /// The monotonic time that the timer was created, so that it can be completed with how
+    /// long it waited.
+    created: Duration,
+    state: Arc<Mutex<AsyncState>>,
+}
+
+impl Entry {
+    /// Nothing is waiting on a timer once its future is dropped, so it can be skipped.
+    fn is_cancelled(&self) -> bool {
+        Arc::strong_count(&self.state) == 1
+    }
+}
+
+struct Level {
+    slots: [Vec<Entry>; SLOTS],
+    /// A bit for each slot that has entries in it.
+    occupied: u64,
+}
+
+struct Wheel {
+    /// The milliseconds since the driver started, as of its last turn.
+    elapsed: u64,
+    levels: [Level; LEVELS],
+}
+
+impl Wheel {
+    fn new() -> Wheel {
+        Wheel {
+            elapsed: 0,
+            levels: std::array::from_fn(|_| Level {
+                slots: std::array::from_fn(|_| Vec::new()),
+                occupied: 0,
+            }),
+        }
+    }
+
+    /// The level and the slot for a deadline, which is after the elapsed time. The level is
+    /// the highest group of bits where the two differ.
+    fn position(&self, deadline: u64) -> (usize, usize) {
+        let when = deadline.min(self.elapsed + MAX_DURATION - 1);
+        let masked = ((self.elapsed ^ when) | (SLOTS as u64 - 1)).min(MAX_DURATION - 1);
+        let level = (63 - masked.leading_zeros() as usize) / SLOT_BITS;
+        let slot = (when >> (level * SLOT_BITS)) as usize % SLOTS;
+        (level, slot)
+    }
+
+    /// Returns the entry when its deadline already passed, so the caller can fire it.
+    fn insert(&mut self, entry: Entry) -> Option<Entry> {
+        if entry.deadline <= self.elapsed {
+            return Some(entry);
+        }
+        let (level, slot) = self.position(entry.deadline);
+        let level = &mut self.levels[level];
+        level.slots[slot].push(entry);
+        level.occupied |= 1 << slot;
+        None
+    }
+
+    /// The start of the next occupied slot of a level. Only the top level can wrap around
+    /// past the current time, as the deadlines in it are clamped to the size of the wheel.
+    fn next_expiration(&self, level: usize) -> Option<(u64, usize)> {
+        let occupied = self.levels[level].occupied;
+        if occupied == 0 {
+            return None;
+        }
+        let slot_range = 1u64 << (level * SLOT_BITS);
+        let level_range = slot_range << SLOT_BITS;
+        let now_slot = (self.elapsed / slot_range) as usize % SLOTS;
+        let slot =
+            (occupied.rotate_right(now_slot as u32).trailing_zeros() as usize + now_slot) % SLOTS;
+        let mut start = (self.elapsed & !(level_range - 1)) + slot as u64 * slot_range;
+        if start <= self.elapsed && level == LEVELS - 1 && slot != now_slot {
+            start += level_range;
+        }
+        Some((start.max(self.elapsed), slot))
+    }
+
+    /// The earliest time that something in the wheel needs to happen, which is either a
+    /// timer firing, or a timer moving down a level.
+    fn next_deadline(&self) -> Option<u64> {
+        (0..LEVELS)
+            .filter_map(|level| self.next_expiration(level))
+            .map(|(start, _)| start)
+            .min()
+    }
+
+    /// Move the time forward, and collect the timers that fired.
+    fn advance(&mut self, now: u64, fired: &mut Vec<Entry>) {
+        while let Some((level, start, slot)) = (0..LEVELS)
+            .filter_map(|level| {
+                self.next_expiration(level)
+                    .map(|(start, slot)| (level, start, slot))
+            })
+            .min_by_key(|(_, start, _)| *start)
+        {
+            if start > now {
+                break;
+            }
+            self.elapsed = start;
+            let entries = mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+            for entry in entries {
+                if entry.is_cancelled() {
+                    continue;
+                }
+                if let Some(entry) = self.insert(entry) {
+                    fired.push(entry);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+    }
+}
+
+struct DriverState {
+    wheel: Wheel,
+    /// Bumped on every new timer, so that the background thread knows to look at the wheel
+    /// again before it waits.
+    generation: u64,
+    shutdown: bool,
+}
+
+struct Inner {
+    clock: Arc<dyn Clock>,
+    /// The monotonic time of the driver's millisecond 0.
+    start: Duration,
+    state: Mutex<DriverState>,
+    /// Wakes the background thread for a new timer, or to shut down.
+    condvar: Condvar,
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, DriverState> {
+        self.state.lock().expect("Failed to lock the timer driver.")
+    }
+
+    /// The driver's milliseconds for a deadline, rounded up so that timers never fire early.
+    fn deadline_millis(&self, deadline: Duration) -> u64 {
+        let since = deadline.saturating_sub(self.start);
+        since.as_millis() as u64 + u64::from(!since.subsec_nanos().is_multiple_of(1_000_000))
+    }
+
+    /// Fire the timers, and return the next deadline along with the generation that it is
+    /// up to date with.
+    fn turn(&self) -> (Option<Duration>, u64) {
+        let now = self.clock.monotonic();
+        let mut fired = Vec::new();
+        let (next, generation) = {
+            let mut state = self.lock();
+            let millis = now.saturating_sub(self.start).as_millis() as u64;
+            state.wheel.advance(millis, &mut fired);
+            (state.wheel.next_deadline(), state.generation)
+        };
+        for entry in fired {
+            entry
+                .state
+                .lock()
+                .expect("Failed to lock shared state mutex")
+                .complete(now.saturating_sub(entry.created));
+        }
+        let next = next.map(|millis| self.start + Duration::from_millis(millis));
+        (next, generation)
+    }
+
+    fn run_thread(&self) {
+        loop {
+            let (next, generation) = self.turn();
+            let state = self.lock();
+            let is_waiting = |state: &mut DriverState| {
+                !state.shutdown
+                    && state.generation == generation
+                    && next.is_none_or(|next| self.clock.monotonic() < next)
+            };
+            let state = match next {
+                Some(next) => {
+                    let timeout = next.saturating_sub(self.clock.monotonic());
+                    self.condvar
+                        .wait_timeout_while(state, timeout, is_waiting)
+                        .expect("Failed to lock the timer driver.")
+                        .0
+                }
+                None => self
+                    .condvar
+                    .wait_while(state, is_waiting)
+                    .expect("Failed to lock the timer driver."),
+            };
+            if state.shutdown {
+                break;
+            }
+        }
+    }
+}
+
+/// Owns the timing wheel, and the background thread when there is one. Dropping the driver
+/// stops its thread, after which its timers only fire from [TimerDriver::turn].
+pub struct TimerDriver {
+    inner: Arc<Inner>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TimerDriver {
+    /// A driver that only fires its timers when it is turned.
+    pub fn new(clock: impl Clock + 'static) -> TimerDriver {
+        let start = clock.monotonic();
+        TimerDriver {
+            inner: Arc::new(Inner {
+                clock: Arc::new(clock),
+                start,
+                state: Mutex::new(DriverState {
+                    wheel: Wheel::new(),
+                    generation: 0,
+                    shutdown: false,
+                }),
+                condvar: Condvar::new(),
+            }),
+            thread: None,
+        }
+    }
+
+    /// A driver with a background thread that waits for each deadline on the real time. A
+    /// mock clock doesn't move while the thread waits, so turn those by hand instead.
+    pub fn with_thread(clock: impl Clock + 'static) -> TimerDriver {
+        let mut driver = TimerDriver::new(clock);
+        let inner = driver.inner.clone();
+        driver.thread = Some(
+            thread::Builder::new()
+                .name("timer driver".into())
+                .spawn(move || inner.run_thread())
+                .expect("Failed to spawn the timer driver thread."),
+        );
+        driver
+    }
+
+    /// Fire the timers whose deadlines have passed on the clock, and return the monotonic
+    /// time of the next deadline, if there is one.
+    pub fn turn(&self) -> Option<Duration> {
+        self.inner.turn().0
+    }
+
+    pub fn handle(&self) -> TimerHandle {
+        TimerHandle {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Drop for TimerDriver {
+    fn drop(&mut self) {
+        self.inner.lock().shutdown = true;
+        self.inner.condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("The timer driver thread panicked.");
+        }
+    }
+}
+
+/// Creates timers on a [TimerDriver].
+#[derive(Clone)]
+pub struct TimerHandle {
+    inner: Arc<Inner>,
+}
+
+impl TimerHandle {
+    /// The handle of a driver on the system clock, with its own thread, that lives for the
+    /// rest of the process.
+    pub fn global() -> TimerHandle {
+        static DRIVER: OnceLock<TimerDriver> = OnceLock::new();
+        DRIVER
+            .get_or_init(|| TimerDriver::with_thread(SystemClock))
+            .handle()
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.inner.clock
+    }
+
+    /// Add a timer to the wheel, which is completed with how long it waited.
+    pub(super) fn register(&self, deadline: Duration) -> Arc<Mutex<AsyncState>> {
+        let created = self.inner.clock.monotonic();
+        let state = Arc::new(Mutex::new(AsyncState::new()));
+        let entry = Entry {
+            deadline: self.inner.deadline_millis(deadline),
+            created,
+            state: state.clone(),
+        };
+        let fired = {
+            let mut driver_state = self.inner.lock();
+            driver_state.generation += 1;
+            driver_state.wheel.insert(entry)
+        };
+        self.inner.condvar.notify_all();
+        if let Some(entry) = fired {
+            entry
+                .state
+                .lock()
+                .expect("Failed to lock shared state mutex")
+                .complete(Duration::ZERO);
+        }
+        state
+    }
+
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        self.sleep_until(self.inner.clock.monotonic() + duration)
+    }
+
+    /// Sleep until a monotonic time of the driver's clock.
+    pub fn sleep_until(&self, deadline: Duration) -> Sleep {
+        Sleep {
+            deadline,
+            state: self.register(deadline),
+        }
+    }
+
+    /// Ticks every period, starting one period from now.
+    pub fn interval(&self, period: Duration) -> Interval {
+        assert!(
+            !period.is_zero(),
+            "The interval period must be more than 0."
+        );
+        let next = self.inner.clock.monotonic() + period;
+        Interval {
+            handle: self.clone(),
+            period,
+            sleep: self.sleep_until(next),
+        }
+    }
+
+    /// Run the future, unless it takes longer than the duration.
+    pub fn timeout<F: Future>(&self, future: F, duration: Duration) -> Timeout<F> {
+        Timeout {
+            future: Box::pin(future),
+            sleep: self.sleep(duration),
+        }
+    }
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    TimerHandle::global().sleep(duration)
+}
+
+pub fn sleep_until(deadline: Duration) -> Sleep {
+    TimerHandle::global().sleep_until(deadline)
+}
+
+pub fn interval(period: Duration) -> Interval {
+    TimerHandle::global().interval(period)
+}
+
+pub fn timeout<F: Future>(future: F, duration: Duration) -> Timeout<F> {
+    TimerHandle::global().timeout(future, duration)
+}
+
+/// Resolves once the driver fires its deadline. Dropping it cancels the timer.
+pub struct Sleep {
+    deadline: Duration,
+    state: Arc<Mutex<AsyncState>>,
+}
+
+impl Sleep {
+    /// The monotonic time that the sleep ends.
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.state
+            .lock()
+            .expect("Failed to lock shared state mutex.")
+            .result
+            .is_some()
+    }
+}
+
+/// The timer stays in the wheel until its slot comes up, so let go of the task's waker now
+/// rather than keeping the task alive until then.
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.waker = None;
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Failed to lock shared state mutex.");
+        if state.result.is_some() {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Ticks at a fixed period. When ticks are missed, the next ones come right away until it
+/// catches up, so the ticks stay on the same schedule.
+pub struct Interval {
+    handle: TimerHandle,
+    period: Duration,
+    sleep: Sleep,
+}
+
+impl Interval {
+    /// Wait for the next tick, and return its scheduled monotonic time.
+    pub async fn tick(&mut self) -> Duration {
+        (&mut self.sleep).await;
+        let deadline = self.sleep.deadline;
+        self.sleep = self.handle.sleep_until(deadline + self.period);
+        deadline
+    }
+}
+
+/// The error when a [Timeout] ran out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Resolves to the output of its future, or an error when the timer fires first. The future
+/// gets polled first, so one that is ready at the deadline still wins.
+pub struct Timeout<F> {
+    future: Pin<Box<F>>,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut self.sleep).poll(cx).map(|_| Err(Elapsed))
+    }
+}
+
+#[test]
+fn test_wheel_levels() {
+    let mut wheel = Wheel::new();
+    let state = Arc::new(Mutex::new(AsyncState::new()));
+    let deadlines = [1, 63, 64, 65, 4_095, 4_096, 300_000, MAX_DURATION * 3 + 7];
+    for deadline in deadlines.iter().rev() {
+        let entry = Entry {
+            deadline: *deadline,
+            created: Duration::ZERO,
+            state: state.clone(),
+        };
+        assert!(wheel.insert(entry).is_none());
+    }
+    assert_eq!(wheel.position(63), (0, 63));
+    assert_eq!(wheel.position(64), (1, 1));
+    assert_eq!(wheel.position(4_096), (2, 1));
+
+    // Each timer fires on its own millisecond, even after moving down levels.
+    let mut fired_at = Vec::new();
+    while let Some(next) = wheel.next_deadline() {
+        let mut fired = Vec::new();
+        wheel.advance(next, &mut fired);
+        fired_at.extend(fired.iter().map(|entry| (wheel.elapsed, entry.deadline)));
+    }
+    let expected: Vec<_> = deadlines
+        .iter()
+        .map(|deadline| (*deadline, *deadline))
+        .collect();
+    assert_eq!(fired_at, expected);
+
+    // Jumping far ahead fires everything in between at once.
+    let mut wheel = Wheel::new();
+    for deadline in [10, 5_000, 70_000] {
+        wheel.insert(Entry {
+            deadline,
+            created: Duration::ZERO,
+            state: state.clone(),
+        });
+    }
+    let mut fired = Vec::new();
+    wheel.advance(100_000, &mut fired);
+    assert_eq!(fired.len(), 3);
+    assert_eq!(wheel.elapsed, 100_000);
+    assert_eq!(wheel.next_deadline(), None);
+}
+
+#[test]
+fn test_timer_cancel() {
+    let clock = crate::clock::MockClock::new(crate::calendar::EpochMS(0));
+    let driver = TimerDriver::new(clock.clone());
+    let handle = driver.handle();
+    let kept = handle.sleep(Duration::from_millis(10));
+    let dropped = handle.sleep(Duration::from_millis(5));
+    assert_eq!(driver.turn(), Some(Duration::from_millis(5)));
+
+    drop(dropped);
+    clock.advance(Duration::from_millis(5));
+    assert_eq!(driver.turn(), Some(Duration::from_millis(10)));
+    assert!(!kept.is_elapsed());
+    clock.advance(Duration::from_millis(5));
+    assert_eq!(driver.turn(), None);
+    assert!(kept.is_elapsed());
+
+    // A deadline in the past is already elapsed.
+    assert!(handle.sleep_until(Duration::ZERO).is_elapsed());
+}
+
+#[test]
+fn test_timer_futures() {
+    use super::Executor;
+    use crate::clock::MockClock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let clock = MockClock::new(crate::calendar::EpochMS(0));
+    let driver = TimerDriver::new(clock.clone());
+    let handle = driver.handle();
+    let (executor, spawner) = Executor::with_workers(2);
+
+    let ticks = {
+        let mut interval = handle.interval(Duration::from_millis(30));
+        assert_eq!(interval.sleep.deadline(), Duration::from_millis(30));
+        spawner.spawn(async move {
+            let mut ticks = Vec::new();
+            for _ in 0..3 {
+                ticks.push(interval.tick().await.as_millis());
+            }
+            ticks
+        })
+    };
+    let timeouts = {
+        let handle = handle.clone();
+        spawner.spawn(async move {
+            let never = handle.timeout(futures::future::pending::<()>(), Duration::from_millis(50));
+            let ready = handle.timeout(async { 7 }, Duration::from_millis(50));
+            let slept = handle.timeout(
+                handle.sleep(Duration::from_millis(20)),
+                Duration::from_millis(50),
+            );
+            (never.await, ready.await, slept.await)
+        })
+    };
+    let results = Arc::new(Mutex::new(None));
+    {
+        let results = results.clone();
+        spawner.spawn(async move {
+            *results.lock().unwrap() = Some((ticks.await.unwrap(), timeouts.await.unwrap()));
+        });
+    }
+    drop(spawner);
+
+    // Move the mock time forward a millisecond at a time, until the tasks are done.
+    let done = Arc::new(AtomicBool::new(false));
+    let ticker = {
+        let done = done.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                clock.advance(Duration::from_millis(1));
+                driver.turn();
+                thread::yield_now();
+            }
+        })
+    };
+    executor.run();
+    done.store(true, Ordering::SeqCst);
+    ticker.join().unwrap();
+
+    let (ticks, timeouts) = results.lock().unwrap().take().unwrap();
+    assert_eq!(ticks, [30, 60, 90]);
+    assert_eq!(timeouts, (Err(Elapsed), Ok(7), Ok(())));
+}
+
+#[test]
+fn test_global_timer() {
+    use super::Executor;
+
+    // A thousand sleeps share the one driver thread.
+    let (executor, spawner) = Executor::with_workers(2);
+    let handles: Vec<_> = (0..1_000)
+        .map(|i| spawner.spawn(async move { sleep(Duration::from_millis(i % 20)).await }))
+        .collect();
+    let timed_out = spawner.spawn(timeout(sleep(Duration::from_secs(60)), Duration::ZERO));
+    let ticks = spawner.spawn(async {
+        let mut interval = interval(Duration::from_millis(5));
+        let first = interval.tick().await;
+        let second = interval.tick().await;
+        sleep_until(second + Duration::from_millis(5)).await;
+        second - first
+    });
+    drop(spawner);
+    executor.run();
+    for handle in handles {
+        assert!(handle.is_finished());
+    }
+    assert!(timed_out.is_finished());
+    assert!(ticks.is_finished());
+}
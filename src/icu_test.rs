@@ -69,6 +69,7 @@ mod test_language_identifier {
 mod test_providers {
     extern crate test;
     use self::test::{black_box, Bencher};
+    use crate::calendar::{bench_closed_form, bench_iterative, EpochMS};
     use std::path::PathBuf;
 
     use icu::datetime::{date::MockDateTime, options, DateTimeFormat, DateTimeFormatOptions};
@@ -201,4 +202,29 @@ mod test_providers {
             black_box(formatter.format(&date));
         });
     }
+
+    // February 1, 2021
+    const NEAR: EpochMS = EpochMS(1_612_199_945_123);
+    // December 31, 9999
+    const FAR: EpochMS = EpochMS(253_402_300_799_999);
+
+    #[bench]
+    fn bench_closed_form_near(bencher: &mut Bencher) {
+        bencher.iter(|| bench_closed_form(black_box(NEAR)));
+    }
+
+    #[bench]
+    fn bench_closed_form_far(bencher: &mut Bencher) {
+        bencher.iter(|| bench_closed_form(black_box(FAR)));
+    }
+
+    #[bench]
+    fn bench_iterative_near(bencher: &mut Bencher) {
+        bencher.iter(|| bench_iterative(black_box(NEAR)));
+    }
+
+    #[bench]
+    fn bench_iterative_far(bencher: &mut Bencher) {
+        bencher.iter(|| bench_iterative(black_box(FAR)));
+    }
 }
@@ -5,6 +5,7 @@ extern crate icu;
 extern crate icu_provider_fs;
 mod atomics;
 mod calendar;
+mod clock;
 mod floats;
 mod futures;
 mod icu_test;